@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// An artifact archived by a build
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildArtifact {
+    pub file_name: String,
+    pub relative_path: String,
+    pub url: String,
+    /// size in bytes, as announced by Jenkins
+    pub size: Option<u64>,
+    /// MD5 of the file, known if it was fingerprinted
+    pub md5: Option<String>,
+}
+
+impl BuildArtifact {
+    /// Where this artifact is downloaded in `dest`, keeping its relative path
+    pub fn destination(&self, dest: &Path) -> PathBuf {
+        self.relative_path
+            .split('/')
+            .filter(|segment| !segment.is_empty() && *segment != "..")
+            .fold(dest.to_path_buf(), |path, segment| path.join(segment))
+    }
+}
+
+/// An artifact written to disk
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadedArtifact {
+    pub relative_path: String,
+    pub path: String,
+    /// size of the file on disk
+    pub size: u64,
+    /// bytes transferred, less than `size` when a partial download was resumed
+    pub downloaded: u64,
+    /// the MD5 of the file matched its fingerprint
+    pub verified: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BuildArtifacts {
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) artifacts: Vec<jenkins_api::build::Artifact>,
+    #[serde(default)]
+    pub(crate) fingerprint: Vec<Fingerprint>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Fingerprint {
+    file_name: String,
+    hash: String,
+}
+
+impl BuildArtifacts {
+    /// MD5 recorded for an artifact, matching fingerprints by path then by file name
+    pub(crate) fn md5_of(&self, artifact: &jenkins_api::build::Artifact) -> Option<String> {
+        self.fingerprint
+            .iter()
+            .find(|fingerprint| fingerprint.file_name == artifact.relative_path)
+            .or_else(|| {
+                self.fingerprint
+                    .iter()
+                    .find(|fingerprint| fingerprint.file_name == artifact.file_name)
+            })
+            .map(|fingerprint| fingerprint.hash.to_lowercase())
+    }
+}
+
+pub(crate) fn md5_of_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut context = md5::Context::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            read => context.consume(&buffer[..read]),
+        }
+    }
+    Ok(format!("{:x}", context.compute()))
+}
@@ -0,0 +1,355 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use jenkins_api::build::BuildStatus;
+use serde::Serialize;
+
+use crate::exit_code::Outcome;
+use crate::polling::{sleep_unless, Backoff, Polling};
+
+/// How a job of a batch ended
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchBuild {
+    pub name: String,
+    pub number: Option<u32>,
+    /// result of the build, CANCELLED if it was cancelled while queued,
+    /// SKIPPED if it was never triggered, or ERROR if it could not be
+    /// triggered or checked
+    pub status: String,
+    /// duration of the build, in milliseconds
+    pub duration: Option<i64>,
+    pub url: Option<String>,
+    #[serde(skip)]
+    outcome: Outcome,
+}
+
+enum State {
+    Pending,
+    Queued(jenkins_api::queue::ShortQueueItem),
+    /// running build, with its number, progress, and seconds left according
+    /// to its estimated duration
+    Running(jenkins_api::queue::ShortQueueItem, u32, u32, Option<i64>),
+    Done(BatchBuild),
+}
+
+struct Job {
+    name: String,
+    state: State,
+    /// whether the build was asked to stop, it is only checked afterwards
+    stopping: bool,
+}
+
+impl Job {
+    fn done(&mut self, status: &str, outcome: Outcome) {
+        let number = match self.state {
+            State::Running(_, number, ..) => Some(number),
+            _ => None,
+        };
+        self.state = State::Done(BatchBuild {
+            name: self.name.clone(),
+            number,
+            status: status.to_string(),
+            duration: None,
+            url: None,
+            outcome,
+        });
+    }
+
+    fn is_active(&self) -> bool {
+        matches!(self.state, State::Queued(_) | State::Running(..))
+    }
+
+    /// Delay before checking the job again
+    fn next_poll(&self, polling: Polling) -> Duration {
+        match self.state {
+            State::Running(_, _, _, remaining) => polling.next(true, remaining),
+            _ => polling.next(false, None),
+        }
+    }
+
+    fn progress(&self) -> String {
+        match &self.state {
+            State::Pending => format!("{} pending", self.name),
+            State::Queued(_) => format!("{} queued", self.name),
+            State::Running(_, number, progress, _) => {
+                format!("{} #{} {}%", self.name, number, progress)
+            }
+            State::Done(build) => match build.number {
+                Some(number) => format!("{} #{} {}", self.name, number, build.status),
+                None => format!("{} {}", self.name, build.status),
+            },
+        }
+    }
+}
+
+/// Trigger jobs, with at most `max_parallel` of them queued or running at
+/// the same time, and wait for all of them to finish. With `fail_fast`, the
+/// first build that does not succeed stops the others and skips the ones not
+/// triggered yet.
+///
+/// Progress is written on stderr after each check, and the errors at the end.
+/// Server errors while checking are retried with a growing delay, a job that
+/// can not be triggered or checked ends as ERROR without stopping the others.
+/// On Ctrl-C, offers to abort the builds as with `fail_fast`. Returns how each
+/// job ended, in the order of `names`, and the outcome of the first build that
+/// did not succeed.
+pub fn run(
+    jenkins: &jencli::JenkinsInformation,
+    names: Vec<String>,
+    parameters: &jencli::parameters::BuildParameters,
+    max_parallel: Option<usize>,
+    fail_fast: bool,
+    polling: Polling,
+) -> Result<(Vec<BatchBuild>, Option<Outcome>), failure::Error> {
+    let max_parallel = max_parallel.unwrap_or(names.len()).max(1);
+    let mut jobs = names
+        .into_iter()
+        .map(|name| Job {
+            name,
+            state: State::Pending,
+            stopping: false,
+        })
+        .collect::<Vec<_>>();
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::SIGINT, Arc::clone(&interrupted))?;
+    // a second Ctrl-C quits right away
+    signal_hook::cleanup::register(signal_hook::SIGINT, vec![signal_hook::SIGINT])?;
+    let mut errors = vec![];
+    let result = drive(
+        jenkins,
+        &mut jobs,
+        parameters,
+        max_parallel,
+        fail_fast,
+        polling,
+        &interrupted,
+        &mut errors,
+    );
+    signal_hook::cleanup::cleanup_signal(signal_hook::SIGINT)?;
+    for err in errors {
+        eprintln!("error: {}", err);
+    }
+    let outcome = result?;
+
+    let builds = jobs
+        .into_iter()
+        .filter_map(|job| match job.state {
+            State::Done(build) => Some(build),
+            _ => None,
+        })
+        .collect();
+    Ok((builds, outcome.or(Some(Outcome::Success))))
+}
+
+/// Check the jobs until all of them are done, collecting the errors met on
+/// the way, and return the outcome of the first one that did not succeed
+#[allow(clippy::too_many_arguments)]
+fn drive(
+    jenkins: &jencli::JenkinsInformation,
+    jobs: &mut [Job],
+    parameters: &jencli::parameters::BuildParameters,
+    max_parallel: usize,
+    fail_fast: bool,
+    polling: Polling,
+    interrupted: &AtomicBool,
+    errors: &mut Vec<String>,
+) -> Result<Option<Outcome>, failure::Error> {
+    let mut outcome = None;
+    let mut failed = false;
+    let mut last_progress = String::new();
+    let mut backoff = Backoff::default();
+
+    'checks: loop {
+        let mut active = jobs.iter().filter(|job| job.is_active()).count();
+        for job in jobs.iter_mut() {
+            if active >= max_parallel || failed {
+                break;
+            }
+            if let State::Pending = job.state {
+                match jencli::trigger_job(jenkins, &job.name, parameters) {
+                    Ok(item) => {
+                        job.state = State::Queued(item);
+                        active += 1;
+                    }
+                    Err(err) => {
+                        errors.push(format!("could not trigger {}: {}", job.name, err));
+                        job.done("ERROR", Outcome::Error);
+                    }
+                }
+            }
+        }
+
+        for job in jobs.iter_mut() {
+            if let Err(err) = poll(jenkins, job) {
+                match backoff.retry(&err) {
+                    Some(delay) => {
+                        eprintln!("{}, retrying in {}", err, humantime::format_duration(delay));
+                        if sleep_unless(interrupted, delay) {
+                            stop_on_interrupt(interrupted, &mut failed, &mut outcome)?;
+                        }
+                        continue 'checks;
+                    }
+                    None => {
+                        errors.push(format!("could not check {}: {}", job.name, err));
+                        job.done("ERROR", Outcome::Error);
+                    }
+                }
+            }
+            if let State::Done(ref build) = job.state {
+                if outcome.is_none() && build.outcome != Outcome::Success {
+                    outcome = Some(build.outcome);
+                    failed = fail_fast;
+                }
+            }
+        }
+        backoff = Backoff::default();
+        if failed {
+            // a job that could not be stopped is still checked until it ends
+            for job in jobs.iter_mut() {
+                if let Err(err) = abort(jenkins, job) {
+                    errors.push(format!("could not stop {}: {}", job.name, err));
+                }
+            }
+        }
+
+        let finished = jobs
+            .iter()
+            .filter(|job| matches!(job.state, State::Done(_)))
+            .count();
+        let progress = format!(
+            "{}/{} finished: {}",
+            finished,
+            jobs.len(),
+            jobs.iter()
+                .map(Job::progress)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if progress != last_progress {
+            eprintln!("{}", progress);
+            last_progress = progress;
+        }
+        if finished == jobs.len() {
+            break;
+        }
+        let delay = jobs
+            .iter()
+            .filter(|job| job.is_active())
+            .map(|job| job.next_poll(polling))
+            .min()
+            .unwrap_or_else(|| polling.next(false, None));
+        if sleep_unless(interrupted, delay) {
+            stop_on_interrupt(interrupted, &mut failed, &mut outcome)?;
+        }
+    }
+    Ok(outcome)
+}
+
+/// Ask whether to abort the builds of the batch after Ctrl-C, failing the
+/// batch if so, or returning an error to stop waiting for them
+fn stop_on_interrupt(
+    interrupted: &AtomicBool,
+    failed: &mut bool,
+    outcome: &mut Option<Outcome>,
+) -> Result<(), failure::Error> {
+    interrupted.store(false, Ordering::SeqCst);
+    if !crate::ask_yes_no("\nAbort the builds of the batch on Jenkins?")? {
+        return Err(failure::err_msg(
+            "stopped waiting, the builds of the batch are still queued or running on Jenkins",
+        ));
+    }
+    *failed = true;
+    outcome.get_or_insert(Outcome::Aborted);
+    Ok(())
+}
+
+/// Check a job queued or running, moving it along its states
+fn poll(jenkins: &jencli::JenkinsInformation, job: &mut Job) -> Result<(), jencli::Error> {
+    let item = match &job.state {
+        State::Queued(item) | State::Running(item, ..) => item.clone(),
+        State::Pending | State::Done(_) => return Ok(()),
+    };
+    let queue_item = jencli::get_queue_item(jenkins, &item)?;
+    let number = match queue_item.executable {
+        Some(build) => build.number,
+        None if queue_item.cancelled == Some(true) => {
+            job.done("CANCELLED", Outcome::Aborted);
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+
+    let build = jencli::get_build(jenkins, &job.name, Some(number))?;
+    job.state = match build.result {
+        Some(result) if !build.building => State::Done(BatchBuild {
+            name: job.name.clone(),
+            number: Some(number),
+            status: status_name(result).to_string(),
+            duration: Some(build.duration),
+            url: Some(build.url),
+            outcome: Outcome::from(result),
+        }),
+        _ => {
+            let elapsed = chrono::Utc::now().timestamp_millis() - build.timestamp as i64;
+            let (progress, remaining) = match build.estimated_duration {
+                estimated if estimated > 0 => (
+                    (elapsed * 100 / estimated).clamp(0, 99) as u32,
+                    Some((estimated - elapsed).max(0) / 1000),
+                ),
+                _ => (0, None),
+            };
+            State::Running(item, number, progress, remaining)
+        }
+    };
+    Ok(())
+}
+
+/// Stop a job of a failed batch: cancel it if queued, stop it once if
+/// running, or skip it if not triggered yet
+fn abort(jenkins: &jencli::JenkinsInformation, job: &mut Job) -> Result<(), jencli::Error> {
+    let number = match job.state {
+        State::Pending => {
+            job.done("SKIPPED", Outcome::NotBuilt);
+            return Ok(());
+        }
+        State::Queued(ref item) => {
+            let queue_item = jencli::get_queue_item(jenkins, item)?;
+            match queue_item.executable {
+                Some(build) => {
+                    job.state = State::Running(item.clone(), build.number, 0, None);
+                    build.number
+                }
+                None => {
+                    jencli::cancel_queue_item(jenkins, queue_item.id as i32)?;
+                    job.done("CANCELLED", Outcome::Aborted);
+                    return Ok(());
+                }
+            }
+        }
+        State::Running(_, number, ..) if !job.stopping => number,
+        State::Running(..) | State::Done(_) => return Ok(()),
+    };
+    // the build is checked again until it is aborted
+    job.stopping = true;
+    jencli::stop_build(
+        jenkins,
+        &job.name,
+        Some(number),
+        jencli::StopSignal::Stop,
+        Duration::from_secs(0),
+    )?;
+    Ok(())
+}
+
+fn status_name(result: BuildStatus) -> &'static str {
+    match result {
+        BuildStatus::Success => "SUCCESS",
+        BuildStatus::Unstable => "UNSTABLE",
+        BuildStatus::Failure => "FAILURE",
+        BuildStatus::Aborted => "ABORTED",
+        BuildStatus::NotBuilt => "NOT_BUILT",
+    }
+}
@@ -1,17 +1,27 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use config::{Config, ConfigError, Environment, Source, Value};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use structopt::{clap::AppSettings, StructOpt};
 
-#[derive(StructOpt, Debug)]
+use crate::credentials;
+use crate::exit_code::{parse_exit_code, Outcome};
+use crate::output::OutputFormat;
+
+#[derive(StructOpt, Debug, Clone)]
 pub enum CommandOpt {
     /// search for a job
     #[structopt(name = "search")]
     Search {
-        /// pattern used to search through jobs name
+        /// pattern used to search through jobs name, including the folders containing them
         pattern: String,
+        /// how many levels of folders to search through
+        #[structopt(long = "max-depth", default_value = "3")]
+        max_depth: u8,
         /// format of the output on stdout
         #[structopt(
             long = "tmpl",
@@ -24,7 +34,7 @@ pub enum CommandOpt {
     /// get informations about a job
     #[structopt(name = "job")]
     Job {
-        /// exact name of the job
+        /// exact name of the job, with folders separated by `/`
         name: String,
         /// format of the output on stdout
         #[structopt(
@@ -35,10 +45,147 @@ pub enum CommandOpt {
         template: String,
     },
 
+    /// get, compare or update the configuration of a job
+    #[structopt(name = "config")]
+    Config(ConfigOpt),
+
+    /// enable a job
+    #[structopt(name = "enable")]
+    Enable {
+        /// exact name of the job, with folders separated by `/`
+        #[structopt(required_unless = "all-matching")]
+        name: Option<String>,
+        /// enable all jobs matching this regex instead of a single job
+        #[structopt(long = "all-matching", conflicts_with = "name")]
+        all_matching: Option<String>,
+        /// how many levels of folders to search through with --all-matching
+        #[structopt(long = "max-depth", default_value = "3")]
+        max_depth: u8,
+        /// do not ask for confirmation
+        #[structopt(long = "yes", short = "y")]
+        yes: bool,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ fullName }}\t{{colored color }}"
+        )]
+        template: String,
+    },
+
+    /// disable a job
+    #[structopt(name = "disable")]
+    Disable {
+        /// exact name of the job, with folders separated by `/`
+        #[structopt(required_unless = "all-matching")]
+        name: Option<String>,
+        /// disable all jobs matching this regex instead of a single job
+        #[structopt(long = "all-matching", conflicts_with = "name")]
+        all_matching: Option<String>,
+        /// how many levels of folders to search through with --all-matching
+        #[structopt(long = "max-depth", default_value = "3")]
+        max_depth: u8,
+        /// do not ask for confirmation
+        #[structopt(long = "yes", short = "y")]
+        yes: bool,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ fullName }}\t{{colored color }}"
+        )]
+        template: String,
+    },
+
+    /// copy a job, with its configuration but without its builds
+    #[structopt(name = "copy")]
+    Copy {
+        /// exact name of the job, with folders separated by `/`
+        name: String,
+        /// name of the new job, with folders separated by `/`
+        new_name: String,
+        /// format of the output on stdout
+        #[structopt(long = "tmpl", short = "t", default_value = "copied to {{ fullName }}")]
+        template: String,
+    },
+
+    /// rename a job, keeping its builds
+    #[structopt(name = "rename")]
+    Rename {
+        /// exact name of the job, with folders separated by `/`
+        name: String,
+        /// new name of the job, in the same folder
+        new_name: String,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "renamed to {{ fullName }}"
+        )]
+        template: String,
+    },
+
+    /// delete a job and its builds, after confirmation
+    #[structopt(name = "delete")]
+    Delete {
+        /// exact name of the job, with folders separated by `/`
+        #[structopt(required_unless = "all-matching")]
+        name: Option<String>,
+        /// delete all jobs matching this regex instead of a single job
+        #[structopt(long = "all-matching", conflicts_with = "name")]
+        all_matching: Option<String>,
+        /// how many levels of folders to search through with --all-matching
+        #[structopt(long = "max-depth", default_value = "3")]
+        max_depth: u8,
+        /// do not ask for confirmation
+        #[structopt(long = "yes", short = "y")]
+        yes: bool,
+        /// format of the output on stdout
+        #[structopt(long = "tmpl", short = "t", default_value = "deleted {{ fullName }}")]
+        template: String,
+    },
+
+    /// trigger several jobs and wait for all of them to finish
+    #[structopt(name = "batch")]
+    Batch {
+        /// exact names of the jobs, with folders separated by `/`
+        #[structopt(required_unless_one = &["all-matching", "jobs-file"])]
+        names: Vec<String>,
+        /// also trigger all jobs matching this regex
+        #[structopt(long = "all-matching")]
+        all_matching: Option<String>,
+        /// also trigger the jobs listed in this file, one per line, ignoring lines starting with `#`
+        #[structopt(long = "jobs-file", parse(from_os_str))]
+        jobs_file: Option<PathBuf>,
+        /// how many levels of folders to search through with --all-matching
+        #[structopt(long = "max-depth", default_value = "3")]
+        max_depth: u8,
+        /// parameter to pass to each build, as KEY=VALUE
+        #[structopt(long = "param", short = "p", parse(try_from_str = parse_key_value))]
+        params: Vec<(String, String)>,
+        /// how many jobs can be queued or running at the same time, all by default
+        #[structopt(long = "max-parallel")]
+        max_parallel: Option<usize>,
+        /// stop the other builds as soon as one does not succeed
+        #[structopt(long = "fail-fast")]
+        fail_fast: bool,
+        /// check builds status every X seconds, showing progress on stderr with every check; by
+        /// default, checks get closer as builds near the end of their estimated duration
+        #[structopt(long = "polling")]
+        polling: Option<u64>,
+        /// format of the output on stdout, once all builds are finished
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ name }}\t{{#if number}}#{{ number }}{{/if}}\t{{colored status }}{{#if duration}}\t{{ duration }}ms{{/if}}"
+        )]
+        template: String,
+    },
+
     /// get informations about a build
     #[structopt(name = "build")]
     Build {
-        /// name of the job
+        /// name of the job, with folders separated by `/`
         name: String,
         /// number of the build, will fetch lastBuild if not specified
         number: Option<u32>,
@@ -51,25 +198,334 @@ pub enum CommandOpt {
         template: String,
     },
 
+    /// list the last builds of a job
+    #[structopt(name = "builds")]
+    Builds {
+        /// name of the job, with folders separated by `/`
+        name: String,
+        /// number of most recent builds to look at, before filtering
+        #[structopt(long = "last", short = "n", default_value = "20")]
+        last: u32,
+        /// only keep builds with this result, can be repeated
+        #[structopt(long = "result", parse(try_from_str = parse_build_status))]
+        results: Vec<jenkins_api::build::BuildStatus>,
+        /// only keep builds started after this date, as YYYY-MM-DD or RFC 3339
+        #[structopt(long = "since", parse(try_from_str = parse_date))]
+        since: Option<u64>,
+        /// only keep builds started before this date, as YYYY-MM-DD (included) or RFC 3339
+        #[structopt(long = "until", parse(try_from_str = parse_end_date))]
+        until: Option<u64>,
+        /// only keep builds started by this user, using their id or name
+        #[structopt(long = "user")]
+        user: Option<String>,
+        /// only keep builds of this branch
+        #[structopt(long = "branch")]
+        branch: Option<String>,
+        /// display statistics over the selected builds instead of the builds
+        #[structopt(long = "stats")]
+        stats: bool,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ displayName }}\t{{#if building}}building{{else}}{{colored result }}{{/if}}\t{{date timestamp }}\t{{ duration }}ms"
+        )]
+        template: String,
+        /// format of the output on stdout with --stats
+        #[structopt(
+            long = "stats-tmpl",
+            default_value = "{{ builds }} builds, {{ successRate }}% success, duration mean {{ meanDuration }}ms p50 {{ p50Duration }}ms p95 {{ p95Duration }}ms, {{ flakiness }}% flakiness"
+        )]
+        stats_template: String,
+    },
+
+    /// get the test results of a build
+    #[structopt(name = "tests")]
+    Tests {
+        /// name of the job, with folders separated by `/`
+        name: String,
+        /// number of the build, will fetch lastBuild if not specified
+        number: Option<u32>,
+        /// include the stack traces of failing tests
+        #[structopt(long = "stack-traces")]
+        stack_traces: bool,
+        /// compare failing tests with this previous build
+        #[structopt(long = "diff")]
+        diff: Option<u32>,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ passCount }} passed, {{ failCount }} failed, {{ skipCount }} skipped{{#each failures}}\n{{colored status }} {{ className }}.{{ name }}{{#if errorDetails}}\n    {{ errorDetails }}{{/if}}{{#if errorStackTrace}}\n{{ errorStackTrace }}{{/if}}{{/each}}"
+        )]
+        template: String,
+        /// format of the output on stdout with --diff
+        #[structopt(
+            long = "diff-tmpl",
+            default_value = "{{len newFailures }} new failures, {{len fixed }} fixed, {{len stillFailing }} still failing{{#each newFailures}}\nnew\t{{ className }}.{{ name }}{{/each}}{{#each fixed}}\nfixed\t{{ className }}.{{ name }}{{/each}}{{#each stillFailing}}\nstill\t{{ className }}.{{ name }}{{/each}}"
+        )]
+        diff_template: String,
+    },
+
+    /// list or download the artifacts of a build
+    #[structopt(name = "artifacts")]
+    Artifacts {
+        /// name of the job, with folders separated by `/`
+        name: String,
+        /// number of the build, will fetch lastBuild if not specified
+        #[structopt(conflicts_with = "latest-successful")]
+        number: Option<u32>,
+        /// use the last successful build instead of the last build
+        #[structopt(long = "latest-successful")]
+        latest_successful: bool,
+        /// download the artifacts whose relative path matches this glob pattern, like `**/*.jar`
+        #[structopt(long = "download")]
+        download: Option<glob::Pattern>,
+        /// directory where artifacts are downloaded, keeping their relative paths
+        #[structopt(long = "dest", default_value = ".", parse(from_os_str))]
+        dest: PathBuf,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ relativePath }}\t{{ size }}"
+        )]
+        template: String,
+        /// format of the output on stdout with --download
+        #[structopt(
+            long = "download-tmpl",
+            default_value = "{{ path }}\t{{ size }}{{#if verified}}\tmd5 ok{{/if}}"
+        )]
+        download_template: String,
+    },
+
+    /// get the console output of a build
+    #[structopt(name = "log")]
+    Log {
+        /// name of the job, with folders separated by `/`
+        name: String,
+        /// number of the build, will fetch lastBuild if not specified
+        number: Option<u32>,
+        /// keep streaming the console output until the build is finished
+        #[structopt(long = "follow", short = "f")]
+        follow: bool,
+        /// check for new output every X seconds when following
+        #[structopt(long = "polling", default_value = "2")]
+        polling: u64,
+    },
+
+    /// list the stages of a Pipeline build
+    #[structopt(name = "stages")]
+    Stages {
+        /// name of the job, with folders separated by `/`
+        name: String,
+        /// number of the build, will fetch lastBuild if not specified
+        number: Option<u32>,
+        /// print the console output of this stage instead of listing stages
+        #[structopt(long = "log")]
+        log: Option<String>,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ name }}\t{{colored status }}\t{{ durationMillis }}ms{{#if pauseDurationMillis}} (paused {{ pauseDurationMillis }}ms){{/if}}"
+        )]
+        template: String,
+    },
+
+    /// list the inputs a Pipeline build is waiting for, or proceed with or abort one
+    #[structopt(name = "input")]
+    Input {
+        /// name of the job, with folders separated by `/`
+        name: String,
+        /// number of the build, will use lastBuild if not specified
+        number: Option<u32>,
+        /// id of the input to answer, needed when several are pending
+        #[structopt(long = "id")]
+        id: Option<String>,
+        /// proceed with the input
+        #[structopt(long = "proceed", conflicts_with = "abort")]
+        proceed: bool,
+        /// abort the input, and the build with it
+        #[structopt(long = "abort")]
+        abort: bool,
+        /// value of a parameter of the input when proceeding, as KEY=VALUE
+        #[structopt(
+            long = "param",
+            short = "p",
+            parse(try_from_str = parse_key_value),
+            requires = "proceed"
+        )]
+        params: Vec<(String, String)>,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ id }}\t{{ message }}{{#if answer}}\t{{#if answer.proceeded}}proceeded{{else}}aborted{{/if}} by {{ answer.user }}{{#each answer.parameters}} {{@key}}={{this}}{{/each}}{{else}}{{#each inputs}}\n  {{ name }}{{#if defaultValue}}={{ defaultValue }}{{/if}}{{#if choices}} ({{#each choices}}{{#if @index}}, {{/if}}{{this}}{{/each}}){{/if}}{{#if description}}\t{{ description }}{{/if}}{{/each}}{{/if}}"
+        )]
+        template: String,
+    },
+
+    /// trigger a job again with the parameters of one of its builds
+    #[structopt(name = "rebuild")]
+    Rebuild {
+        /// exact name of the job, with folders separated by `/`
+        name: String,
+        /// number of the build, will use lastBuild if not specified
+        number: Option<u32>,
+        /// parameter to change from the build, as KEY=VALUE
+        #[structopt(long = "param", short = "p", parse(try_from_str = parse_key_value))]
+        params: Vec<(String, String)>,
+        /// wait for the job to start before returning
+        #[structopt(long = "wait-start")]
+        wait_start: bool,
+        /// wait for the job to finish before returning
+        #[structopt(long = "wait-finish")]
+        wait_finish: bool,
+        /// check job status every X seconds, and display status with every check; by default,
+        /// checks get closer as the build nears the end of its estimated duration
+        #[structopt(long = "polling")]
+        polling: Option<u64>,
+        /// stop waiting if the build has not started after this long in the queue, like 30s, 5m or 1h
+        #[structopt(long = "queue-timeout", parse(try_from_str = humantime::parse_duration))]
+        queue_timeout: Option<Duration>,
+        /// stop waiting if the build is still running after this long, like 30s, 5m or 1h
+        #[structopt(long = "build-timeout", parse(try_from_str = humantime::parse_duration))]
+        build_timeout: Option<Duration>,
+        /// cancel the queue item or stop the build when a timeout is reached
+        #[structopt(long = "abort-on-timeout")]
+        abort_on_timeout: bool,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ queueItem.task.name }} {{#if queueItem.why}}{{ queueItem.why }}{{/if}}{{#if queueItem.executable}}{{ build.displayName }} {{colored build.result }} {{build.elapsed}}s (est. {{ build.estimatedDuration }}ms{{#if build.remaining}}, {{ build.remaining }}s left{{/if}}){{#if build.stage}} [{{ build.stage.name }}]{{/if}}{{/if}}"
+        )]
+        template: String,
+    },
+
+    /// run a Pipeline build again with a modified Jenkinsfile
+    #[structopt(name = "replay")]
+    Replay {
+        /// exact name of the job, with folders separated by `/`
+        name: String,
+        /// number of the build, will use lastBuild if not specified
+        number: Option<u32>,
+        /// Pipeline script to run instead of the one of the build
+        #[structopt(long = "script", parse(from_os_str))]
+        script: PathBuf,
+        /// wait for the job to start before returning
+        #[structopt(long = "wait-start")]
+        wait_start: bool,
+        /// wait for the job to finish before returning
+        #[structopt(long = "wait-finish")]
+        wait_finish: bool,
+        /// check job status every X seconds, and display status with every check; by default,
+        /// checks get closer as the build nears the end of its estimated duration
+        #[structopt(long = "polling")]
+        polling: Option<u64>,
+        /// stop waiting if the build has not started after this long in the queue, like 30s, 5m or 1h
+        #[structopt(long = "queue-timeout", parse(try_from_str = humantime::parse_duration))]
+        queue_timeout: Option<Duration>,
+        /// stop waiting if the build is still running after this long, like 30s, 5m or 1h
+        #[structopt(long = "build-timeout", parse(try_from_str = humantime::parse_duration))]
+        build_timeout: Option<Duration>,
+        /// cancel the queue item or stop the build when a timeout is reached
+        #[structopt(long = "abort-on-timeout")]
+        abort_on_timeout: bool,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ queueItem.task.name }} {{#if queueItem.why}}{{ queueItem.why }}{{/if}}{{#if queueItem.executable}}{{ build.displayName }} {{colored build.result }} {{build.elapsed}}s (est. {{ build.estimatedDuration }}ms{{#if build.remaining}}, {{ build.remaining }}s left{{/if}}){{#if build.stage}} [{{ build.stage.name }}]{{/if}}{{/if}}"
+        )]
+        template: String,
+    },
+
     /// trigger a job
     #[structopt(name = "trigger")]
     Trigger {
-        /// exact name of the job
+        /// exact name of the job, with folders separated by `/`
         name: String,
+        /// parameter to pass to the build, as KEY=VALUE
+        #[structopt(long = "param", short = "p", parse(try_from_str = parse_key_value))]
+        params: Vec<(String, String)>,
+        /// file to upload as a file parameter of the build, as KEY=PATH
+        #[structopt(long = "file", short = "f", parse(try_from_str = parse_key_value))]
+        files: Vec<(String, String)>,
+        /// JSON or HOCON file with parameters to pass to the build, overriden by --param
+        #[structopt(long = "params-file", parse(from_os_str))]
+        params_file: Option<PathBuf>,
         /// wait for the job to start before returning
         #[structopt(long = "wait-start")]
         wait_start: bool,
         /// wait for the job to finish before returning
         #[structopt(long = "wait-finish")]
         wait_finish: bool,
-        /// check job status every X seconds, and display status with every check
-        #[structopt(long = "polling", default_value = "10")]
-        polling: u64,
+        /// stream the console output of the build until it finishes
+        #[structopt(long = "log")]
+        log: bool,
+        /// check job status every X seconds, and display status with every check; by default,
+        /// checks get closer as the build nears the end of its estimated duration
+        #[structopt(long = "polling")]
+        polling: Option<u64>,
+        /// stop waiting if the build has not started after this long in the queue, like 30s, 5m or 1h
+        #[structopt(long = "queue-timeout", parse(try_from_str = humantime::parse_duration))]
+        queue_timeout: Option<Duration>,
+        /// stop waiting if the build is still running after this long, like 30s, 5m or 1h
+        #[structopt(long = "build-timeout", parse(try_from_str = humantime::parse_duration))]
+        build_timeout: Option<Duration>,
+        /// cancel the queue item or stop the build when a timeout is reached
+        #[structopt(long = "abort-on-timeout")]
+        abort_on_timeout: bool,
         /// format of the output on stdout
         #[structopt(
             long = "tmpl",
             short = "t",
-            default_value = "{{ queueItem.task.name }} {{#if queueItem.why}}{{ queueItem.why }}{{/if}}{{#if queueItem.executable}}{{ build.displayName }} {{colored build.result }} {{build.elapsed}}s (est. {{ build.estimatedDuration }}ms){{/if}}"
+            default_value = "{{ queueItem.task.name }} {{#if queueItem.why}}{{ queueItem.why }}{{/if}}{{#if queueItem.executable}}{{ build.displayName }} {{colored build.result }} {{build.elapsed}}s (est. {{ build.estimatedDuration }}ms{{#if build.remaining}}, {{ build.remaining }}s left{{/if}}){{#if build.stage}} [{{ build.stage.name }}]{{/if}}{{/if}}"
+        )]
+        template: String,
+    },
+
+    /// stop a running build
+    #[structopt(name = "stop")]
+    Stop {
+        /// exact name of the job, with folders separated by `/`
+        #[structopt(required_unless = "all-matching")]
+        name: Option<String>,
+        /// number of the build, will stop lastBuild if not specified
+        number: Option<u32>,
+        /// stop all running builds of jobs matching this regex instead of a single build
+        #[structopt(long = "all-matching", conflicts_with = "name")]
+        all_matching: Option<String>,
+        /// terminate the build if it is still running after the grace period
+        #[structopt(long = "term")]
+        term: bool,
+        /// terminate then kill the build if it is still running after the grace periods
+        #[structopt(long = "kill")]
+        kill: bool,
+        /// wait X seconds for the build to stop before escalating
+        #[structopt(long = "grace", default_value = "10")]
+        grace: u64,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ fullDisplayName }} {{colored result }}{{#if building}} still running{{/if}}"
+        )]
+        template: String,
+    },
+
+    /// cancel a queued item
+    #[structopt(name = "cancel")]
+    Cancel {
+        /// id of the queue item, as shown by `running`
+        id: i32,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "cancelled {{ task.name }} ({{ id }})"
         )]
         template: String,
     },
@@ -84,7 +540,82 @@ pub enum CommandOpt {
         #[structopt(
             long = "tmpl",
             short = "t",
-            default_value = "{{#if queueItem}}{{ queueItem.task.name }} {{#if queueItem.why}}{{ queueItem.why }}{{/if}}{{/if}}{{#if build}}{{#if build.fullDisplayName}}{{ build.fullDisplayName }}{{else}}Unknown Task{{/if}}{{#if build.result}} {{colored build.result }}{{/if}} {{#if build.elapsed}}{{build.elapsed}}s {{/if}}{{#if build.estimatedDuration}}(est. {{ build.estimatedDuration }}ms) {{/if}}- {{ build.progress }}% on {{ build.node}} {{/if}}"
+            default_value = "{{#if queueItem}}{{ queueItem.task.name }} {{#if queueItem.why}}{{ queueItem.why }}{{/if}}{{/if}}{{#if build}}{{#if build.fullDisplayName}}{{ build.fullDisplayName }}{{else}}Unknown Task{{/if}}{{#if build.result}} {{colored build.result }}{{/if}} {{#if build.elapsed}}{{build.elapsed}}s {{/if}}{{#if build.estimatedDuration}}(est. {{ build.estimatedDuration }}ms) {{/if}}{{#if build.remaining}}{{ build.remaining }}s left {{/if}}- {{ build.progress }}% on {{ build.node}} {{/if}}"
+        )]
+        template: String,
+    },
+
+    /// list nodes, the master and its agents
+    #[structopt(name = "nodes")]
+    Nodes {
+        /// pattern used to search through nodes name
+        pattern: Option<String>,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ name }}\t{{#if online}}online{{else}}offline{{#if offlineCause}} ({{ offlineCause }}){{/if}}{{/if}}\t{{ busyExecutors }}/{{ executors }} busy\t{{#each labels}}{{ this }} {{/each}}"
+        )]
+        template: String,
+    },
+
+    /// put a node offline or back online
+    #[structopt(name = "node")]
+    Node(NodeOpt),
+
+    /// check an API token against Jenkins and store it for the current profile
+    #[structopt(name = "login")]
+    Login {
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "logged in as {{ fullName }}"
+        )]
+        template: String,
+    },
+
+    /// full-screen dashboard of executors, queue and failing jobs, refreshed automatically
+    #[structopt(
+        name = "tui",
+        after_help = "Keys:\n  tab: next pane, up/down: select, enter: open the log, esc: close the log\n  s: stop the build or cancel the queued item, t: trigger the job, r: refresh, q: quit"
+    )]
+    Tui {
+        /// view whose failing jobs are listed, prefixed by its folder separated by `/` if any
+        #[structopt(long = "view", default_value = "all")]
+        view: String,
+        /// refresh every X seconds
+        #[structopt(long = "refresh", default_value = "5")]
+        refresh: u64,
+    },
+
+    /// print a completion script, completing job and view names from Jenkins
+    #[structopt(name = "completions")]
+    Completions {
+        /// shell to complete for
+        #[structopt(possible_values = &["bash", "zsh", "fish"])]
+        shell: structopt::clap::Shell,
+    },
+
+    /// names of jobs or views starting with a prefix, used by completion scripts
+    #[structopt(name = "__complete", setting = AppSettings::Hidden)]
+    Complete {
+        #[structopt(possible_values = &["jobs", "views"])]
+        kind: String,
+        prefix: Option<String>,
+        /// reuse names fetched less than X seconds ago
+        #[structopt(long = "ttl", default_value = "300")]
+        ttl: u64,
+    },
+
+    /// list Jenkins servers configured as profiles
+    #[structopt(name = "profiles")]
+    Profiles {
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{#if default}}*{{else}} {{/if}} {{ name }}\t{{ url }}\t{{#each sources}}{{ this }} {{/each}}"
         )]
         template: String,
     },
@@ -102,7 +633,7 @@ pub enum CommandOpt {
     /// list jobs of a view
     #[structopt(name = "view")]
     View {
-        /// exact name of the view
+        /// exact name of the view, prefixed by its folder separated by `/` if any
         name: String,
         /// format of the output on stdout
         #[structopt(
@@ -114,6 +645,155 @@ pub enum CommandOpt {
     },
 }
 
+impl CommandOpt {
+    /// Commands only reading from Jenkins, that can be run again with --watch
+    pub fn is_listing(&self) -> bool {
+        match self {
+            CommandOpt::Search { .. }
+            | CommandOpt::Job { .. }
+            | CommandOpt::Build { .. }
+            | CommandOpt::Builds { .. }
+            | CommandOpt::Tests { .. }
+            | CommandOpt::Running { .. }
+            | CommandOpt::Nodes { .. }
+            | CommandOpt::Views { .. }
+            | CommandOpt::View { .. } => true,
+            CommandOpt::Artifacts { download, .. } => download.is_none(),
+            CommandOpt::Stages { log, .. } => log.is_none(),
+            CommandOpt::Input { proceed, abort, .. } => !proceed && !abort,
+            _ => false,
+        }
+    }
+
+    /// Whether the command lists builds of a job, the newest one first
+    pub fn is_listing_builds(&self) -> bool {
+        match self {
+            CommandOpt::Build { .. } => true,
+            CommandOpt::Builds { stats, .. } => !stats,
+            _ => false,
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum NodeOpt {
+    /// mark a node temporarily offline, letting its running builds finish
+    #[structopt(name = "offline")]
+    Offline {
+        /// name of the node, as shown by `nodes`
+        name: String,
+        /// why the node is offline, shown in Jenkins
+        #[structopt(long = "reason")]
+        reason: Option<String>,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ name }}\t{{#if online}}online{{else}}offline{{#if offlineCause}} ({{ offlineCause }}){{/if}}{{/if}}"
+        )]
+        template: String,
+    },
+
+    /// bring back online a node marked offline
+    #[structopt(name = "online")]
+    Online {
+        /// name of the node, as shown by `nodes`
+        name: String,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{ name }}\t{{#if online}}online{{else}}offline{{#if offlineCause}} ({{ offlineCause }}){{/if}}{{/if}}"
+        )]
+        template: String,
+    },
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum ConfigOpt {
+    /// print the config.xml of a job
+    #[structopt(name = "get")]
+    Get {
+        /// exact name of the job, with folders separated by `/`
+        name: String,
+    },
+
+    /// compare the config.xml of a job with a local file, ignoring formatting
+    #[structopt(name = "diff")]
+    Diff {
+        /// exact name of the job, with folders separated by `/`
+        name: String,
+        /// local config.xml
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// format of the output on stdout, for each difference
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{#if old}}- {{ path }}: {{ old }}{{#if new}}\n{{/if}}{{/if}}{{#if new}}+ {{ path }}: {{ new }}{{/if}}"
+        )]
+        template: String,
+    },
+
+    /// update the config.xml of a job from a local file, creating the job if needed
+    #[structopt(name = "push")]
+    Push {
+        /// exact name of the job, with folders separated by `/`
+        name: String,
+        /// local config.xml
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// only show what would change, without updating the job
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+        /// format of the output on stdout
+        #[structopt(
+            long = "tmpl",
+            short = "t",
+            default_value = "{{#if dryRun}}would {{#if created}}create{{else}}update{{/if}}{{else}}{{#if created}}created{{else}}updated{{/if}}{{/if}} {{ name }} ({{len changes}} changes){{#if dryRun}}{{#each changes}}\n  {{#if old}}- {{ path }}: {{ old }}{{#if new}}\n  {{/if}}{{/if}}{{#if new}}+ {{ path }}: {{ new }}{{/if}}{{/each}}{{/if}}"
+        )]
+        template: String,
+    },
+}
+
+fn parse_build_status(s: &str) -> Result<jenkins_api::build::BuildStatus, String> {
+    serde_json::from_value(serde_json::Value::String(s.to_uppercase())).map_err(|_| {
+        format!(
+            "unknown result {}, expected one of SUCCESS, UNSTABLE, FAILURE, ABORTED, NOT_BUILT",
+            s
+        )
+    })
+}
+
+/// Parse a date as a timestamp in milliseconds, like the ones of Jenkins builds
+fn parse_date(s: &str) -> Result<u64, String> {
+    let datetime = match chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(date) => DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc),
+        Err(_) => DateTime::parse_from_rfc3339(s)
+            .map_err(|err| format!("invalid date {}: {}", s, err))?
+            .with_timezone(&Utc),
+    };
+    Ok(datetime.timestamp_millis() as u64)
+}
+
+/// Parse a date like `parse_date`, a day ending at its last millisecond to be included
+fn parse_end_date(s: &str) -> Result<u64, String> {
+    match chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(date) => Ok(
+            DateTime::<Utc>::from_utc(date.and_hms_milli(23, 59, 59, 999), Utc).timestamp_millis()
+                as u64,
+        ),
+        Err(_) => parse_date(s),
+    }
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let pos = s
+        .find('=')
+        .ok_or_else(|| format!("invalid KEY=VALUE: no `=` found in `{}`", s))?;
+    Ok((s[..pos].to_string(), s[pos + 1..].to_string()))
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(
     setting(AppSettings::ColoredHelp),
@@ -124,31 +804,132 @@ Templates are defined using handlebars syntax. To view all fields available for
 A few helpers are available:
 * colored: add color to build result and job status
 * date: transform timestamps to UTC dates
+* len: number of elements of a list
+
+About Output Formats
+With --output, items are written as JSON lines (json), a JSON array (json-array), YAML (yaml), CSV (csv) or an
+aligned table (table) instead of using the template. Columns of csv and table outputs default to all fields, and
+can be selected with --columns, for example `--columns name,color,lastBuild.number`.
+
+About Exit Codes
+`build` and `trigger`, when waiting for the build to finish, exit with a code depending on the build result, and
+`batch` with the code of the first build that did not succeed:
+SUCCESS=0, FAILURE=1, UNSTABLE=2, ABORTED=3, NOT_BUILT=4 and TIMEOUT=5. `build` exits with RUNNING=6 when the build
+has no result yet. Each code can be changed with --exit-code, for example `--exit-code UNSTABLE=0`. Other commands
+exit with 0, and any command exits with 10 when jencli itself fails, for example on invalid arguments, an unknown job
+or when Jenkins can not be reached.
+A build cancelled while queued exits as ABORTED. With --queue-timeout or --build-timeout, `trigger`, `rebuild` and
+`replay` stop waiting, or following the log, and exit as TIMEOUT, cancelling the queue item or stopping the build with
+--abort-on-timeout. On Ctrl-C while waiting, they and `batch` ask whether to abort the builds on Jenkins.
+
+About Watching
+With --watch, listing commands like `running`, `job`, `view` or `build` are run again every few seconds. Lines that
+changed since the previous run are marked with `>`. Watching stops on Ctrl-C, or once an item matches --until-field,
+for example `jencli --watch 10 --until-field color=blue job my-job`, or with `build` and `builds` once the newest
+build has one of the --until-result.
 
 About Configuration
 Jenkins configuration (url, user, password, depth) can be overriden in a number of way, by decreasing order of priority:
 * values passed as options
 * values in environment variables
-* .jencli.yaml file in path
-* .jencli.yaml file in user home directory
+* .jencli.conf file in path
+* .jencli.conf file in user home directory
+
+About Credentials
+The password or API token is taken, by decreasing order of priority, from:
+* --password or JENKINS_PASSWORD
+* the file given with --token-file or JENKINS_TOKEN_FILE
+* the `password` key in .jencli.conf
+* the output of the `password_command` key in .jencli.conf, for example `password_command = "pass show jenkins"`
+* the file given with the `token_file` key in .jencli.conf
+* the token stored by `jencli login` for the current profile
+Token files must not be accessible by other users.
+
+About Profiles
+Several Jenkins servers can be configured in .jencli.conf files as profiles, for example `profiles.prod { url = ..., user = ... }`.
+A profile is selected with --profile or JENKINS_PROFILE, or with the `profile` key in .jencli.conf. Its values override
+the ones defined at the root of .jencli.conf files.
+
+About Completion
+`jencli completions bash` (or zsh, fish) prints a completion script, for example to load with
+`source <(jencli completions bash)`. Names of jobs and views are completed from Jenkins, and kept in a cache for
+five minutes.
 "#
 )]
 pub struct ParamsOpt {
     /// Jenkins URL
     #[structopt(env = "JENKINS_URL", long = "url")]
-    pub url: String,
+    pub url: Option<String>,
     /// Jenkins user
     #[structopt(env = "JENKINS_USER", long = "user")]
     pub user: Option<String>,
-    /// Jenkins password
-    #[structopt(env = "JENKINS_PASSWORD", long = "password")]
+    /// Jenkins password or API token
+    #[structopt(env = "JENKINS_PASSWORD", long = "password", hide_env_values = true)]
     pub password: Option<String>,
-    /// Amount of data retrieved from Jenkins
-    #[structopt(env = "JENKINS_DEPTH", long = "depth", default_value = "1")]
-    pub depth: u8,
+    /// File containing the Jenkins API token
+    #[structopt(env = "JENKINS_TOKEN_FILE", long = "token-file", parse(from_os_str))]
+    pub token_file: Option<PathBuf>,
+    /// Amount of data retrieved from Jenkins [default: 1]
+    #[structopt(env = "JENKINS_DEPTH", long = "depth")]
+    pub depth: Option<u8>,
+    /// Output format, `template` uses the template of the command
+    #[structopt(
+        long = "output",
+        short = "o",
+        default_value = "template",
+        possible_values = OutputFormat::variants()
+    )]
+    pub output: OutputFormat,
+    /// Columns of csv and table outputs, as paths like `build.result`
+    #[structopt(long = "columns", use_delimiter = true)]
+    pub columns: Option<Vec<String>>,
+    /// Exit code to use for a build result, as RESULT=CODE
+    #[structopt(
+        long = "exit-code",
+        parse(try_from_str = parse_exit_code),
+        number_of_values = 1
+    )]
+    pub exit_codes: Vec<(Outcome, i32)>,
+    /// Profile from .jencli.conf to use
+    #[structopt(env = "JENKINS_PROFILE", long = "profile")]
+    pub profile: Option<String>,
+    /// Run the command every X seconds, redrawing its output and marking changed lines
+    #[structopt(long = "watch")]
+    pub watch: Option<u64>,
+    /// Stop watching once an item has this value, as FIELD=VALUE like `color=blue`
+    #[structopt(
+        long = "until-field",
+        parse(try_from_str = parse_key_value),
+        number_of_values = 1,
+        requires = "watch"
+    )]
+    pub until_fields: Vec<(String, String)>,
+    /// Stop watching once the build, or the newest one with `builds`, has this result
+    #[structopt(
+        long = "until-result",
+        parse(try_from_str = parse_build_status),
+        number_of_values = 1,
+        requires = "watch"
+    )]
+    pub until_results: Vec<jenkins_api::build::BuildStatus>,
 
     #[structopt(flatten)]
     pub command: CommandOpt,
+
+    /// settings of the selected profile, completed once the options are parsed
+    #[structopt(skip)]
+    pub profile_settings: ProfileSettings,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ProfileSettings {
+    pub url: Option<String>,
+    pub user: Option<String>,
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    pub password_command: Option<String>,
+    pub token_file: Option<String>,
+    pub depth: Option<u8>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -156,7 +937,26 @@ pub struct JenkinsSettings {
     pub url: Option<String>,
     pub user: Option<String>,
     pub password: Option<String>,
+    pub password_command: Option<String>,
+    pub token_file: Option<String>,
     pub depth: Option<u8>,
+    /// profile used if none is selected
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileSettings>,
+    /// configuration files defining each profile
+    #[serde(skip)]
+    pub profile_sources: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    #[serde(flatten)]
+    pub settings: ProfileSettings,
+    pub default: bool,
+    pub sources: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -179,6 +979,27 @@ impl SourceHocon {
         self.required = required;
         self
     }
+    fn profile_names(&self) -> Vec<String> {
+        match &self.conf {
+            Ok(hocon::Hocon::Hash(conf)) => match conf.get("profiles") {
+                Some(hocon::Hocon::Hash(profiles)) => profiles.keys().cloned().collect(),
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+}
+
+fn hocon_to_value(origin: &str, hocon: &hocon::Hocon) -> Value {
+    match hocon {
+        hocon::Hocon::Hash(hash) => Value::new(
+            Some(&origin.to_string()),
+            hash.iter()
+                .map(|(k, v)| (k.clone(), hocon_to_value(origin, v)))
+                .collect::<HashMap<_, _>>(),
+        ),
+        v => Value::new(Some(&origin.to_string()), v.as_string()),
+    }
 }
 
 impl config::Source for SourceHocon {
@@ -187,11 +1008,11 @@ impl config::Source for SourceHocon {
     }
 
     fn collect(&self) -> Result<HashMap<String, Value>, ConfigError> {
-        // Coerce the file contents to a string
+        // Coerce the file contents to strings, keeping the profiles structure
         match &self.conf {
             Ok(hocon::Hocon::Hash(conf)) => Ok(conf
                 .iter()
-                .map(|(k, v)| (k.clone(), Value::new(Some(&self.path), v.as_string())))
+                .map(|(k, v)| (k.clone(), hocon_to_value(&self.path, v)))
                 .collect()),
 
             _ => {
@@ -211,47 +1032,151 @@ impl JenkinsSettings {
     #[allow(clippy::new_ret_no_self)]
     pub fn new() -> Result<Self, ConfigError> {
         let mut config = Config::new();
+        let mut profile_sources: HashMap<String, Vec<String>> = HashMap::new();
 
         let filename = ".jencli.conf";
+        let mut files = vec![];
 
         // Load file from home directory
-        if let Some(mut home_conf) = dirs::home_dir() {
-            home_conf.push(filename);
-            config.merge(SourceHocon::new(home_conf.to_str().unwrap()).required(false))?;
+        if let Some(home_dir) = dirs::home_dir() {
+            files.push(home_dir.join(filename));
         }
 
         // Load file from any folder in the path
         let mut current_dir = env::current_dir().unwrap();
-        let mut pathes = vec![current_dir.clone().into_os_string()];
+        let mut pathes = vec![current_dir.clone()];
         while current_dir.pop() {
-            pathes.push(current_dir.clone().into_os_string());
+            pathes.push(current_dir.clone());
         }
         for dir in pathes.iter().rev() {
-            let mut file = dir.clone();
-            file.push(filename);
-            config.merge(SourceHocon::new(file.to_str().unwrap()).required(false))?;
+            // the home directory may also be in the path
+            let file = dir.join(filename);
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+
+        for file in files {
+            let source = SourceHocon::new(file.to_str().unwrap()).required(false);
+            for profile in source.profile_names() {
+                profile_sources
+                    .entry(profile)
+                    .or_default()
+                    .push(source.path.clone());
+            }
+            config.merge(source)?;
         }
 
         // Load from environment
-        config.merge(Environment::with_prefix("jenkins").separator("_"))?;
+        // `__` separates nested keys, keeping JENKINS_PASSWORD_COMMAND a single key
+        config.merge(Environment::with_prefix("jenkins").separator("__"))?;
+
+        let mut settings: Self = config.try_into()?;
+        settings.profile_sources = profile_sources;
+        Ok(settings)
+    }
+
+    /// Settings of the profile `name`, or of the default profile if none is given,
+    /// completed with settings defined outside of profiles
+    pub fn select(&self, name: Option<&str>) -> Result<ProfileSettings, ConfigError> {
+        let profile = match name.or(self.profile.as_deref()) {
+            Some(name) => self.profiles.get(name).cloned().ok_or_else(|| {
+                ConfigError::Message(format!("profile '{}' is not configured", name))
+            })?,
+            None => ProfileSettings::default(),
+        };
+        Ok(ProfileSettings {
+            url: profile.url.or_else(|| self.url.clone()),
+            user: profile.user.or_else(|| self.user.clone()),
+            password: profile.password.or_else(|| self.password.clone()),
+            password_command: profile
+                .password_command
+                .or_else(|| self.password_command.clone()),
+            token_file: profile.token_file.or_else(|| self.token_file.clone()),
+            depth: profile.depth.or(self.depth),
+        })
+    }
 
-        config.try_into()
+    pub fn list_profiles(self) -> impl Iterator<Item = Profile> {
+        let default = self.profile;
+        let mut sources = self.profile_sources;
+        let mut profiles = self.profiles.into_iter().collect::<Vec<_>>();
+        profiles.sort_by(|(a, _), (b, _)| a.cmp(b));
+        profiles.into_iter().map(move |(name, settings)| Profile {
+            default: default.as_ref() == Some(&name),
+            sources: sources.remove(&name).unwrap_or_default(),
+            name,
+            settings,
+        })
     }
 }
 
-pub fn load() -> Result<ParamsOpt, ConfigError> {
-    let jenkins_settings = JenkinsSettings::new()?;
-    if let Some(url) = jenkins_settings.url {
-        env::set_var("JENKINS_URL", &url);
+/// Password or API token from the options, the profile or a token stored by
+/// `login`. Only resolved for commands talking to Jenkins, as it may run the
+/// password_command or read a token file.
+pub fn load_password(
+    password: Option<String>,
+    token_file: Option<&Path>,
+    profile_name: Option<&str>,
+    profile: &ProfileSettings,
+) -> Result<Option<String>, failure::Error> {
+    if password.is_some() {
+        return Ok(password);
     }
-    if let Some(user) = jenkins_settings.user {
-        env::set_var("JENKINS_USER", &user);
+    if let Some(token_file) = token_file {
+        return Ok(Some(credentials::read_token_file(token_file)?));
     }
-    if let Some(password) = jenkins_settings.password {
-        env::set_var("JENKINS_PASSWORD", &password);
+    if let Some(ref password) = profile.password {
+        return Ok(Some(password.clone()));
     }
-    if let Some(depth) = jenkins_settings.depth {
-        env::set_var("JENKINS_DEPTH", &depth.to_string());
+    if let Some(ref command) = profile.password_command {
+        return Ok(Some(credentials::password_from_command(command)?));
+    }
+    if let Some(ref token_file) = profile.token_file {
+        return Ok(Some(credentials::read_token_file(Path::new(token_file))?));
+    }
+    match credentials::stored_token_path(profile_name) {
+        Some(ref stored) if stored.is_file() => Ok(Some(credentials::read_token_file(stored)?)),
+        _ => Ok(None),
+    }
+}
+
+pub fn load() -> Result<ParamsOpt, failure::Error> {
+    // invalid arguments are tool errors, unlike --help or --version
+    let mut opt = match ParamsOpt::from_iter_safe(std::env::args_os()) {
+        Ok(opt) => opt,
+        Err(err) if err.use_stderr() => {
+            eprintln!("{}", err.message);
+            std::process::exit(crate::exit_code::ERROR);
+        }
+        Err(err) => err.exit(),
+    };
+    let settings = JenkinsSettings::new()?;
+    opt.profile = opt.profile.or_else(|| settings.profile.clone());
+    let profile = settings.select(opt.profile.as_deref())?;
+    opt.url = opt.url.or_else(|| profile.url.clone());
+    opt.user = opt.user.or_else(|| profile.user.clone());
+    opt.depth = opt.depth.or(profile.depth);
+    opt.profile_settings = profile;
+    Ok(opt)
+}
+
+pub fn load_parameters(path: &Path) -> Result<BTreeMap<String, String>, failure::Error> {
+    match hocon::HoconLoader::new().load_file(path)?.hocon()? {
+        hocon::Hocon::Hash(conf) => conf
+            .into_iter()
+            .map(|(k, v)| match v.as_string() {
+                Some(value) => Ok((k, value)),
+                None => Err(failure::format_err!(
+                    "invalid value for parameter '{}' in {}",
+                    k,
+                    path.display()
+                )),
+            })
+            .collect(),
+        _ => Err(failure::format_err!(
+            "error parsing parameters file {}",
+            path.display()
+        )),
     }
-    Ok(ParamsOpt::from_args())
 }
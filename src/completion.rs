@@ -0,0 +1,193 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use structopt::clap::Shell;
+use structopt::StructOpt;
+
+use crate::cli_config::ParamsOpt;
+
+/// Commands whose first argument is the name of a job
+const JOB_COMMANDS: &[&str] = &[
+    "artifacts",
+    "batch",
+    "build",
+    "builds",
+    "copy",
+    "delete",
+    "disable",
+    "enable",
+    "input",
+    "job",
+    "log",
+    "rebuild",
+    "rename",
+    "replay",
+    "stages",
+    "stop",
+    "tests",
+    "trigger",
+];
+
+/// Commands whose first argument is the name of a view
+const VIEW_COMMANDS: &[&str] = &["view"];
+
+/// Job and view names of a Jenkins server, kept on disk between completions
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    url: String,
+    timestamp: i64,
+    jobs: Vec<String>,
+    views: Vec<String>,
+}
+
+/// Completion script generated by clap, with job and view names completed by
+/// calling `jencli __complete`
+pub fn script(shell: Shell) -> String {
+    let mut app = ParamsOpt::clap();
+    // clap 2 can not generate bash completions for a subcommand whose name
+    // contains `__`, and `__complete` is not meant to be completed anyway
+    app.p
+        .subcommands
+        .retain(|subcommand| subcommand.p.meta.name != "__complete");
+    let mut script = vec![];
+    app.gen_completions_to("jencli", shell, &mut script);
+    let script = String::from_utf8_lossy(&script).to_string();
+
+    let live = match shell {
+        Shell::Bash => BASH
+            .replace("JOB_COMMANDS", &JOB_COMMANDS.join("|"))
+            .replace("VIEW_COMMANDS", &VIEW_COMMANDS.join("|")),
+        Shell::Zsh => ZSH
+            .replace("JOB_COMMANDS", &JOB_COMMANDS.join("|"))
+            .replace("VIEW_COMMANDS", &VIEW_COMMANDS.join("|")),
+        Shell::Fish => FISH
+            .replace("JOB_COMMANDS", &JOB_COMMANDS.join(" "))
+            .replace("VIEW_COMMANDS", &VIEW_COMMANDS.join(" ")),
+        _ => String::new(),
+    };
+    format!("{}\n{}", script.trim_end(), live)
+}
+
+/// Names of jobs or views starting with `prefix`, from the cache of `url` if
+/// it is more recent than `ttl`, or from Jenkins. The credentials needed by
+/// `connect` are only resolved when the cache can not be used.
+pub fn complete<F>(
+    url: &str,
+    connect: F,
+    kind: &str,
+    prefix: Option<String>,
+    ttl: time::Duration,
+) -> Result<Vec<String>, failure::Error>
+where
+    F: FnOnce() -> Result<jencli::JenkinsInformation, failure::Error>,
+{
+    let path = cache_path(url);
+    let cached = path
+        .as_ref()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|content| serde_json::from_slice::<Cache>(&content).ok())
+        .filter(|cache| {
+            cache.url == url && Utc::now().timestamp() - cache.timestamp < ttl.as_secs() as i64
+        });
+    let cache = match cached {
+        Some(cache) => cache,
+        None => {
+            let home = jencli::get_home(&connect()?, 3)?;
+            let cache = Cache {
+                url: url.to_string(),
+                timestamp: Utc::now().timestamp(),
+                jobs: home.jobs.into_iter().map(|job| job.name).collect(),
+                views: home.views.into_iter().map(|view| view.name).collect(),
+            };
+            if let Some(path) = path {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&path, serde_json::to_vec(&cache)?)?;
+            }
+            cache
+        }
+    };
+
+    let names = if kind == "views" {
+        cache.views
+    } else {
+        cache.jobs
+    };
+    let prefix = prefix.unwrap_or_default();
+    Ok(names
+        .into_iter()
+        .filter(|name| name.starts_with(&prefix))
+        .collect())
+}
+
+fn cache_path(url: &str) -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| {
+        dir.join("jencli")
+            .join(format!("completion-{:x}.json", md5::compute(url)))
+    })
+}
+
+const BASH: &str = r#"
+_jencli_live() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" command="" position=0 word
+    for word in "${COMP_WORDS[@]:1:COMP_CWORD-1}"; do
+        if [[ -n "$command" && "$word" != -* ]]; then
+            position=$((position + 1))
+        elif [[ -z "$command" && "$word" =~ ^(JOB_COMMANDS|VIEW_COMMANDS)$ ]]; then
+            command="$word"
+        fi
+    done
+    if [[ $position -eq 0 && "$cur" != -* ]]; then
+        case "$command" in
+            JOB_COMMANDS)
+                local IFS=$'\n'
+                COMPREPLY=($(jencli __complete jobs "$cur" 2>/dev/null))
+                return 0
+                ;;
+            VIEW_COMMANDS)
+                local IFS=$'\n'
+                COMPREPLY=($(jencli __complete views "$cur" 2>/dev/null))
+                return 0
+                ;;
+        esac
+    fi
+    _jencli "$@"
+}
+complete -F _jencli_live -o bashdefault -o default jencli
+"#;
+
+const ZSH: &str = r#"
+_jencli_live() {
+    local command="" position=0 word
+    for word in "${words[@]:1:$((CURRENT - 2))}"; do
+        if [[ -n "$command" && "$word" != -* ]]; then
+            position=$((position + 1))
+        elif [[ -z "$command" && "$word" =~ "^(JOB_COMMANDS|VIEW_COMMANDS)$" ]]; then
+            command="$word"
+        fi
+    done
+    if [[ $position -eq 0 && "${words[CURRENT]}" != -* ]]; then
+        case "$command" in
+            JOB_COMMANDS)
+                compadd -- ${(f)"$(jencli __complete jobs "${words[CURRENT]}" 2>/dev/null)"}
+                return
+                ;;
+            VIEW_COMMANDS)
+                compadd -- ${(f)"$(jencli __complete views "${words[CURRENT]}" 2>/dev/null)"}
+                return
+                ;;
+        esac
+    fi
+    _jencli "$@"
+}
+compdef _jencli_live jencli
+"#;
+
+const FISH: &str = r#"
+complete -c jencli -n "__fish_seen_subcommand_from JOB_COMMANDS" -f -a "(jencli __complete jobs (commandline -ct) 2>/dev/null)"
+complete -c jencli -n "__fish_seen_subcommand_from VIEW_COMMANDS" -f -a "(jencli __complete views (commandline -ct) 2>/dev/null)"
+"#;
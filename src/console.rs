@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use std::{thread, time};
+
+use crate::raw_client::RawClient;
+use crate::Error;
+
+/// Part of the console output of a build, as returned by `logText/progressiveText`
+#[derive(Debug)]
+pub(crate) struct ConsoleChunk {
+    pub(crate) text: String,
+    pub(crate) next_start: u64,
+    pub(crate) more_data: bool,
+}
+
+pub(crate) fn get_console_chunk(
+    client: &RawClient,
+    build_path: &str,
+    start: u64,
+) -> Result<ConsoleChunk, Error> {
+    let mut response = RawClient::send(
+        client
+            .get(&format!("{}/logText/progressiveText", build_path))
+            .query(&[("start", start)]),
+    )?;
+
+    let next_start = response
+        .headers()
+        .get("X-Text-Size")
+        .and_then(|size| size.to_str().ok())
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(start);
+    let more_data = response
+        .headers()
+        .get("X-More-Data")
+        .and_then(|more| more.to_str().ok())
+        .map(|more| more == "true")
+        .unwrap_or(false);
+
+    Ok(ConsoleChunk {
+        text: response.text()?,
+        next_start,
+        more_data,
+    })
+}
+
+/// Iterator over the lines of the console output of a build, polling Jenkins
+/// for new output until the build is finished, or until `stop` returns true
+pub(crate) struct ConsoleFollower {
+    client: RawClient,
+    build_path: String,
+    polling: time::Duration,
+    stop: Box<dyn FnMut() -> bool>,
+    start: u64,
+    lines: VecDeque<String>,
+    partial_line: String,
+    first_poll: bool,
+    finished: bool,
+}
+
+impl ConsoleFollower {
+    pub(crate) fn new(
+        client: RawClient,
+        build_path: String,
+        polling: time::Duration,
+        stop: Box<dyn FnMut() -> bool>,
+    ) -> Self {
+        ConsoleFollower {
+            client,
+            build_path,
+            polling,
+            stop,
+            start: 0,
+            lines: VecDeque::new(),
+            partial_line: String::new(),
+            first_poll: true,
+            finished: false,
+        }
+    }
+}
+
+impl ConsoleFollower {
+    /// Wait before the next poll, returning early with `true` once `stop` does
+    fn wait_stopped(&mut self) -> bool {
+        let end = time::Instant::now() + self.polling;
+        while !(self.stop)() {
+            let now = time::Instant::now();
+            if now >= end {
+                return false;
+            }
+            thread::sleep((end - now).min(time::Duration::from_millis(100)));
+        }
+        true
+    }
+}
+
+impl Iterator for ConsoleFollower {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.lines.pop_front() {
+                return Some(Ok(line));
+            }
+            if self.finished {
+                if self.partial_line.is_empty() {
+                    return None;
+                }
+                return Some(Ok(std::mem::take(&mut self.partial_line)));
+            }
+
+            if !self.first_poll && self.wait_stopped() {
+                self.finished = true;
+                continue;
+            }
+            self.first_poll = false;
+
+            let chunk = match get_console_chunk(&self.client, &self.build_path, self.start) {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err));
+                }
+            };
+            self.start = chunk.next_start;
+            self.finished = !chunk.more_data;
+
+            self.partial_line.push_str(&chunk.text);
+            if let Some(last_newline) = self.partial_line.rfind('\n') {
+                let remaining = self.partial_line.split_off(last_newline + 1);
+                self.lines.extend(
+                    self.partial_line
+                        .lines()
+                        .map(std::string::ToString::to_string),
+                );
+                self.partial_line = remaining;
+            }
+        }
+    }
+}
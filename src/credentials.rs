@@ -0,0 +1,82 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use failure::format_err;
+
+/// Run `command` through the shell and use its output as the password
+pub fn password_from_command(command: &str) -> Result<String, failure::Error> {
+    #[cfg(windows)]
+    let output = Command::new("cmd").args(["/C", command]).output()?;
+    #[cfg(not(windows))]
+    let output = Command::new("sh").args(["-c", command]).output()?;
+
+    if !output.status.success() {
+        return Err(format_err!(
+            "password command `{}` failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?.trim_end().to_string())
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<(), failure::Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)?.permissions().mode();
+    if mode & 0o007 != 0 {
+        return Err(format_err!(
+            "token file {} is accessible by other users, restrict its permissions with `chmod 600 {}`",
+            path.display(),
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<(), failure::Error> {
+    Ok(())
+}
+
+/// Read an API token from `path`, refusing files that other users can read
+pub fn read_token_file(path: &Path) -> Result<String, failure::Error> {
+    check_permissions(path)?;
+    Ok(fs::read_to_string(path)?.trim().to_string())
+}
+
+/// Where `jencli login` stores the token of a profile
+pub fn stored_token_path(profile: Option<&str>) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| {
+        dir.join("jencli")
+            .join("tokens")
+            .join(profile.unwrap_or("default"))
+    })
+}
+
+pub fn store_token(path: &Path, token: &str) -> Result<(), failure::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // the file is never readable by others, even before being restricted
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // an existing file keeps its permissions when opened
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    writeln!(file, "{}", token)?;
+    Ok(())
+}
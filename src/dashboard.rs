@@ -0,0 +1,615 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor;
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use jenkins_api::job::BallColor;
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::{Frame, Terminal};
+
+static HELP: &str =
+    "tab: next pane  up/down: select  enter: log  s: stop/cancel  t: trigger  r: refresh  q: quit";
+/// Start of the status line after a refresh failed, until a refresh succeeds
+static REFRESH_FAILED: &str = "refresh failed";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pane {
+    Executors,
+    Queue,
+    Failures,
+}
+
+impl Pane {
+    fn next(self) -> Self {
+        match self {
+            Pane::Executors => Pane::Queue,
+            Pane::Queue => Pane::Failures,
+            Pane::Failures => Pane::Executors,
+        }
+    }
+}
+
+/// Actions triggered by a key, that need Jenkins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    Refresh,
+    OpenLog,
+    Stop,
+    Trigger,
+    /// `y` pressed to confirm a stop or a trigger
+    Confirm,
+}
+
+/// Build or job selected in the dashboard
+struct Target {
+    name: String,
+    number: Option<u32>,
+    queue_id: Option<u32>,
+}
+
+struct Log {
+    name: String,
+    number: Option<u32>,
+    lines: Vec<String>,
+    /// number of lines hidden at the end, to scroll back
+    scroll: usize,
+}
+
+/// State of the dashboard, updated from Jenkins and by keys
+pub struct Dashboard<'a> {
+    jenkins: &'a jencli::JenkinsInformation,
+    view: String,
+    executors: Vec<jencli::BuildingOn>,
+    queue: Vec<jenkins_api::queue::QueueItem>,
+    failures: Vec<jenkins_api::job::ShortJob>,
+    pane: Pane,
+    selected: usize,
+    log: Option<Log>,
+    status: String,
+    /// stop or trigger waiting for `y` in the status line, with its build or job
+    confirming: Option<(Action, Target)>,
+}
+
+fn is_failing(job: &jenkins_api::job::ShortJob) -> bool {
+    matches!(
+        job.color,
+        Some(BallColor::Red)
+            | Some(BallColor::RedAnime)
+            | Some(BallColor::Yellow)
+            | Some(BallColor::YellowAnime)
+    )
+}
+
+impl<'a> Dashboard<'a> {
+    pub fn new(jenkins: &'a jencli::JenkinsInformation, view: String) -> Self {
+        Dashboard {
+            jenkins,
+            view,
+            executors: vec![],
+            queue: vec![],
+            failures: vec![],
+            pane: Pane::Executors,
+            selected: 0,
+            log: None,
+            status: String::new(),
+            confirming: None,
+        }
+    }
+
+    /// Reload executors, queue, failing jobs and the opened log. Errors are
+    /// shown in the status line so that the dashboard survives Jenkins restarts,
+    /// until a refresh succeeds. The log keeps its lines if it can not be reloaded.
+    pub fn refresh(&mut self) {
+        let refreshed = self.try_refresh();
+        let log = self.refresh_log();
+        match (refreshed, log) {
+            (Err(err), _) => self.status = format!("{}: {}", REFRESH_FAILED, err),
+            (Ok(()), Err(err)) => self.status = format!("{} for the log: {}", REFRESH_FAILED, err),
+            (Ok(()), Ok(())) if self.status.starts_with(REFRESH_FAILED) => self.status.clear(),
+            (Ok(()), Ok(())) => (),
+        }
+        self.selected = self.selected.min(self.len().saturating_sub(1));
+    }
+
+    fn try_refresh(&mut self) -> Result<(), jencli::Error> {
+        self.executors = jencli::get_executors(self.jenkins)?.collect();
+        self.queue = jencli::get_queue(self.jenkins)?.collect();
+        self.failures = jencli::list_jobs_of_view(self.jenkins, &self.view)?
+            .filter(is_failing)
+            .collect();
+        Ok(())
+    }
+
+    fn refresh_log(&mut self) -> Result<(), jencli::Error> {
+        if let Some(ref mut log) = self.log {
+            log.lines = jencli::get_console(self.jenkins, &log.name, log.number)?
+                .lines()
+                .map(ToString::to_string)
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        match self.pane {
+            Pane::Executors => self.executors.len(),
+            Pane::Queue => self.queue.len(),
+            Pane::Failures => self.failures.len(),
+        }
+    }
+
+    fn target(&self) -> Option<Target> {
+        let (url, number, queue_id) = match self.pane {
+            Pane::Executors => {
+                let build = self.executors.get(self.selected)?.build.as_ref()?;
+                (&build.url, Some(build.number), None)
+            }
+            Pane::Queue => {
+                let item = self.queue.get(self.selected)?;
+                (&item.task.url, None, Some(item.id))
+            }
+            Pane::Failures => (&self.failures.get(self.selected)?.url, None, None),
+        };
+        Some(Target {
+            name: jencli::job_name_from_url(url).ok()?,
+            number,
+            queue_id,
+        })
+    }
+
+    /// Apply a key: navigation is handled here, other keys return the action to perform
+    pub fn handle_key(&mut self, key: KeyCode) -> Option<Action> {
+        if self.confirming.is_some() {
+            if let KeyCode::Char('y') | KeyCode::Char('Y') = key {
+                return Some(Action::Confirm);
+            }
+            self.confirming = None;
+            self.status = "nothing done".to_string();
+            return None;
+        }
+        match key {
+            KeyCode::Char('q') => return Some(Action::Quit),
+            KeyCode::Char('r') => return Some(Action::Refresh),
+            KeyCode::Char('s') => return Some(Action::Stop),
+            KeyCode::Char('t') => return Some(Action::Trigger),
+            KeyCode::Enter | KeyCode::Char('l') => return Some(Action::OpenLog),
+            KeyCode::Esc => self.log = None,
+            KeyCode::Tab => {
+                self.pane = self.pane.next();
+                self.selected = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') => match self.log {
+                Some(ref mut log) => log.scroll = (log.scroll + 1).min(log.lines.len()),
+                None => self.selected = self.selected.saturating_sub(1),
+            },
+            KeyCode::Down | KeyCode::Char('j') => match self.log {
+                Some(ref mut log) => log.scroll = log.scroll.saturating_sub(1),
+                None => self.selected = (self.selected + 1).min(self.len().saturating_sub(1)),
+            },
+            _ => (),
+        }
+        None
+    }
+
+    /// Perform an action on the selected build or job, its result is shown in the status line
+    pub fn perform(&mut self, action: Action) {
+        if action == Action::Refresh {
+            self.refresh();
+            return;
+        }
+        if action == Action::Confirm {
+            if let Some((action, target)) = self.confirming.take() {
+                let result = match action {
+                    Action::Stop => self.stop(target),
+                    _ => jencli::trigger_job(
+                        self.jenkins,
+                        &target.name,
+                        &jencli::parameters::BuildParameters::new(),
+                    )
+                    .map(|_| format!("triggered {}", target.name)),
+                };
+                self.status = result.unwrap_or_else(|err| err.to_string());
+            }
+            return;
+        }
+        let target = match self.target() {
+            Some(target) => target,
+            None => {
+                self.status = "nothing selected".to_string();
+                return;
+            }
+        };
+        let result = match action {
+            Action::OpenLog => self.open_log(target),
+            Action::Stop if target.queue_id.is_none() && target.number.is_none() => {
+                Ok(format!("{} is not running", target.name))
+            }
+            // a stray key must not abort a build, stops and triggers are confirmed first
+            Action::Stop | Action::Trigger => {
+                let question = match (action, target.queue_id, target.number) {
+                    (Action::Stop, Some(_), _) => format!("cancel {} in the queue?", target.name),
+                    (Action::Stop, None, Some(number)) => {
+                        format!("stop {} #{}?", target.name, number)
+                    }
+                    _ => format!("trigger {}?", target.name),
+                };
+                self.confirming = Some((action, target));
+                Ok(format!("{} y/N", question))
+            }
+            Action::Quit | Action::Refresh | Action::Confirm => return,
+        };
+        self.status = result.unwrap_or_else(|err| err.to_string());
+    }
+
+    fn open_log(&mut self, target: Target) -> Result<String, jencli::Error> {
+        if target.queue_id.is_some() {
+            return Ok(format!("{} has not started yet", target.name));
+        }
+        let lines = jencli::get_console(self.jenkins, &target.name, target.number)?
+            .lines()
+            .map(ToString::to_string)
+            .collect();
+        self.log = Some(Log {
+            name: target.name,
+            number: target.number,
+            lines,
+            scroll: 0,
+        });
+        Ok("esc: back to the dashboard  up/down: scroll".to_string())
+    }
+
+    fn stop(&mut self, target: Target) -> Result<String, jencli::Error> {
+        match (target.queue_id, target.number) {
+            (Some(id), _) => {
+                jencli::cancel_queue_item(self.jenkins, id as i32)?;
+                Ok(format!("cancelled {}", target.name))
+            }
+            (None, Some(number)) => {
+                jencli::stop_build(
+                    self.jenkins,
+                    &target.name,
+                    Some(number),
+                    jencli::StopSignal::Stop,
+                    Duration::from_secs(0),
+                )?;
+                Ok(format!("stopped {} #{}", target.name, number))
+            }
+            (None, None) => Ok(format!("{} is not running", target.name)),
+        }
+    }
+}
+
+fn progress_bar(progress: u32, width: usize) -> String {
+    let done = (progress.min(100) as usize * width) / 100;
+    format!("[{}{}]", "#".repeat(done), "-".repeat(width - done))
+}
+
+fn color_of(job: &jenkins_api::job::ShortJob) -> Color {
+    match job.color {
+        Some(BallColor::Yellow) | Some(BallColor::YellowAnime) => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+fn draw_list<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    title: String,
+    items: Vec<ListItem>,
+    selected: Option<usize>,
+) {
+    let highlight = if selected.is_some() {
+        Style::default().add_modifier(Modifier::REVERSED)
+    } else {
+        Style::default()
+    };
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(highlight)
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    state.select(selected);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, dashboard: &Dashboard) {
+    let size = f.size();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .split(size);
+    let status = if dashboard.status.is_empty() {
+        HELP
+    } else {
+        &dashboard.status
+    };
+    f.render_widget(Paragraph::new(status), rows[1]);
+
+    if let Some(ref log) = dashboard.log {
+        let height = rows[0].height.saturating_sub(2) as usize;
+        let end = log.lines.len() - log.scroll;
+        let lines = log.lines[end.saturating_sub(height)..end]
+            .iter()
+            .map(|line| Spans::from(line.as_str()))
+            .collect::<Vec<_>>();
+        let title = match log.number {
+            Some(number) => format!("{} #{}", log.name, number),
+            None => log.name.clone(),
+        };
+        f.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+            rows[0],
+        );
+        return;
+    }
+
+    let panes = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(40),
+            Constraint::Percentage(30),
+            Constraint::Min(3),
+        ])
+        .split(rows[0]);
+    let selected = |pane: Pane| {
+        if dashboard.pane == pane && dashboard.len() > 0 {
+            Some(dashboard.selected)
+        } else {
+            None
+        }
+    };
+
+    let executors = dashboard
+        .executors
+        .iter()
+        .map(|executor| {
+            let name = executor
+                .build
+                .as_ref()
+                .and_then(|build| build.full_display_name.clone())
+                .unwrap_or_else(|| "Unknown Task".to_string());
+            ListItem::new(format!(
+                "{:<16} {} {:>3}%  {}",
+                executor.node,
+                progress_bar(executor.progress, 20),
+                executor.progress,
+                name
+            ))
+        })
+        .collect();
+    draw_list(
+        f,
+        panes[0],
+        format!("Executors ({})", dashboard.executors.len()),
+        executors,
+        selected(Pane::Executors),
+    );
+
+    let queue = dashboard
+        .queue
+        .iter()
+        .map(|item| {
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{:<30} ", item.task.name)),
+                Span::styled(
+                    item.why.clone().unwrap_or_default(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    draw_list(
+        f,
+        panes[1],
+        format!("Queue ({})", dashboard.queue.len()),
+        queue,
+        selected(Pane::Queue),
+    );
+
+    let failures = dashboard
+        .failures
+        .iter()
+        .map(|job| {
+            ListItem::new(Span::styled(
+                job.name.clone(),
+                Style::default().fg(color_of(job)),
+            ))
+        })
+        .collect();
+    draw_list(
+        f,
+        panes[2],
+        format!(
+            "Failing jobs of {} ({})",
+            dashboard.view,
+            dashboard.failures.len()
+        ),
+        failures,
+        selected(Pane::Failures),
+    );
+}
+
+/// Run the dashboard full screen until `q` is pressed, refreshing it every `refresh`
+pub fn run(mut dashboard: Dashboard, refresh: Duration) -> Result<(), failure::Error> {
+    dashboard.refresh();
+
+    let _screen = FullScreen::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    event_loop(&mut terminal, &mut dashboard, refresh)
+}
+
+/// Raw mode on the alternate screen, restoring the terminal when dropped,
+/// including when unwinding from a panic
+struct FullScreen;
+
+impl FullScreen {
+    fn enter() -> Result<Self, failure::Error> {
+        terminal::enable_raw_mode()?;
+        let screen = FullScreen;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(screen)
+    }
+}
+
+impl Drop for FullScreen {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+    }
+}
+
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    dashboard: &mut Dashboard,
+    refresh: Duration,
+) -> Result<(), failure::Error> {
+    let mut refreshed = Instant::now();
+    loop {
+        terminal.draw(|f| draw(f, dashboard))?;
+
+        let timeout = refresh.checked_sub(refreshed.elapsed()).unwrap_or_default();
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                match dashboard.handle_key(key.code) {
+                    Some(Action::Quit) => return Ok(()),
+                    Some(action) => dashboard.perform(action),
+                    None => (),
+                }
+            }
+        }
+        if refreshed.elapsed() >= refresh {
+            dashboard.refresh();
+            refreshed = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jencli::mock::{Lifecycle, MockJenkins};
+    use jenkins_api::build::BuildStatus;
+    use tui::backend::TestBackend;
+
+    fn jenkins(mock: &MockJenkins) -> jencli::JenkinsInformation {
+        jencli::JenkinsInformation::new(mock.url(), None, None, 1).unwrap()
+    }
+
+    /// Press `keys`, then render the dashboard as text
+    fn render(dashboard: &mut Dashboard, keys: &[KeyCode]) -> String {
+        for key in keys {
+            if let Some(action) = dashboard.handle_key(*key) {
+                dashboard.perform(action);
+            }
+        }
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|f| draw(f, dashboard)).unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(80)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.symbol.as_str())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn panes_list_executors_and_failing_jobs() {
+        let mock = MockJenkins::start();
+        mock.add_job("app", Lifecycle::default());
+        mock.add_job("broken", Lifecycle::default());
+        mock.add_build("broken", Some(BuildStatus::Failure));
+        mock.add_build("app", None);
+        let jenkins = jenkins(&mock);
+        let mut dashboard = Dashboard::new(&jenkins, "all".to_string());
+        dashboard.refresh();
+
+        let text = render(&mut dashboard, &[]);
+        assert!(text.contains("Executors (1)"), "{}", text);
+        assert!(text.contains("app #1"), "{}", text);
+        assert!(text.contains("Failing jobs of all (1)"), "{}", text);
+        assert!(text.contains("broken"), "{}", text);
+    }
+
+    #[test]
+    fn stopping_a_build_is_confirmed() {
+        let mock = MockJenkins::start();
+        mock.add_job("app", Lifecycle::default());
+        mock.add_build("app", None);
+        let jenkins = jenkins(&mock);
+        let mut dashboard = Dashboard::new(&jenkins, "all".to_string());
+        dashboard.refresh();
+
+        let text = render(&mut dashboard, &[KeyCode::Char('s'), KeyCode::Char('n')]);
+        assert!(text.contains("nothing done"), "{}", text);
+        assert!(
+            jencli::get_build(&jenkins, "app", Some(1))
+                .unwrap()
+                .building
+        );
+
+        let text = render(&mut dashboard, &[KeyCode::Char('s')]);
+        assert!(text.contains("stop app #1? y/N"), "{}", text);
+        let text = render(&mut dashboard, &[KeyCode::Char('y')]);
+        assert!(text.contains("stopped app #1"), "{}", text);
+        assert_eq!(
+            jencli::get_build(&jenkins, "app", Some(1)).unwrap().result,
+            Some(BuildStatus::Aborted)
+        );
+    }
+
+    #[test]
+    fn refresh_errors_are_shown_until_a_refresh_succeeds() {
+        let mock = MockJenkins::start();
+        mock.add_job("app", Lifecycle::default());
+        let jenkins = jenkins(&mock);
+        let mut dashboard = Dashboard::new(&jenkins, "team".to_string());
+
+        dashboard.refresh();
+        assert!(
+            dashboard.status.starts_with("refresh failed: "),
+            "{}",
+            dashboard.status
+        );
+        mock.add_view("team", &["app"]);
+        dashboard.refresh();
+        assert_eq!(dashboard.status, "");
+    }
+
+    #[test]
+    fn a_log_that_can_not_be_reloaded_does_not_stop_the_refresh() {
+        let mock = MockJenkins::start();
+        mock.add_job("app", Lifecycle::default());
+        mock.add_build("app", None);
+        let jenkins = jenkins(&mock);
+        let mut dashboard = Dashboard::new(&jenkins, "all".to_string());
+        dashboard.refresh();
+        dashboard.perform(Action::OpenLog);
+        let lines = dashboard.log.as_ref().unwrap().lines.clone();
+        assert!(!lines.is_empty());
+
+        mock.fail_requests_to("/job/app/1/consoleText");
+        mock.add_job("lib", Lifecycle::default());
+        mock.add_build("lib", None);
+        dashboard.refresh();
+        assert!(
+            dashboard.status.starts_with("refresh failed for the log: "),
+            "{}",
+            dashboard.status
+        );
+        assert_eq!(dashboard.executors.len(), 2);
+        assert_eq!(dashboard.log.as_ref().unwrap().lines, lines);
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+
+use failure::Fail;
+use reqwest::StatusCode;
+
+/// Errors returned by the functions of this crate
+#[derive(Debug)]
+pub enum Error {
+    /// A search pattern is not a valid regular expression
+    InvalidPattern {
+        pattern: String,
+        cause: regex::Error,
+    },
+    /// Parameters given to a build do not match the definitions of the job
+    InvalidParameter(String),
+    /// Jenkins refused the credentials
+    Unauthorized { url: String },
+    /// The user is not allowed to do this action
+    PermissionDenied { url: String },
+    /// The job, build or item does not exist
+    NotFound { url: String },
+    /// Jenkins answered with an error status
+    Server { status: u16, url: String },
+    /// A downloaded file does not match the checksum recorded by Jenkins
+    ChecksumMismatch {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+    /// A job configuration is not a valid XML document
+    InvalidConfig(String),
+    /// Jenkins could not be reached
+    Network(reqwest::Error),
+    /// Any other error, like an unexpected response
+    Other(failure::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidPattern { pattern, cause } => {
+                write!(f, "invalid pattern '{}': {}", pattern, cause)
+            }
+            Error::InvalidParameter(message) => write!(f, "{}", message),
+            Error::Unauthorized { url } => write!(f, "authentication failed for {}", url),
+            Error::PermissionDenied { url } => write!(f, "permission denied for {}", url),
+            Error::NotFound { url } => write!(f, "not found: {}", url),
+            Error::Server { status, url } => write!(f, "Jenkins returned {} for {}", status, url),
+            Error::ChecksumMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch for {}: expected {}, got {}",
+                path, expected, actual
+            ),
+            Error::InvalidConfig(message) => write!(f, "invalid job configuration: {}", message),
+            Error::Network(err) => write!(f, "could not reach Jenkins: {}", err),
+            Error::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            Error::InvalidPattern { cause, .. } => Some(cause),
+            Error::Network(err) => Some(err),
+            Error::Other(err) => Some(err.as_fail()),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    pub(crate) fn from_status(status: StatusCode, url: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Error::Unauthorized { url },
+            StatusCode::FORBIDDEN => Error::PermissionDenied { url },
+            StatusCode::NOT_FOUND => Error::NotFound { url },
+            status => Error::Server {
+                status: status.as_u16(),
+                url,
+            },
+        }
+    }
+
+    /// The error may not happen again, like Jenkins being restarted
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Server { status, .. } => *status >= 500,
+            Error::Network(_) => true,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn invalid_pattern(pattern: &str, cause: regex::Error) -> Self {
+        Error::InvalidPattern {
+            pattern: pattern.to_string(),
+            cause,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        match (err.status(), err.url()) {
+            (Some(status), Some(url)) => Error::from_status(status, url.to_string()),
+            (None, _) if err.is_http() => Error::Network(err),
+            _ => Error::Other(err.into()),
+        }
+    }
+}
+
+impl From<failure::Error> for Error {
+    fn from(err: failure::Error) -> Self {
+        match err.downcast::<reqwest::Error>() {
+            Ok(err) => err.into(),
+            Err(err) => match err.downcast::<Error>() {
+                Ok(err) => err,
+                Err(err) => Error::Other(err),
+            },
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Other(err.into())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Other(err.into())
+    }
+}
+
+impl From<reqwest::UrlError> for Error {
+    fn from(err: reqwest::UrlError) -> Self {
+        Error::Other(err.into())
+    }
+}
+
+impl From<reqwest::header::ToStrError> for Error {
+    fn from(err: reqwest::header::ToStrError) -> Self {
+        Error::Other(err.into())
+    }
+}
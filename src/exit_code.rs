@@ -0,0 +1,108 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use jenkins_api::build::BuildStatus;
+
+/// Exit code when jencli itself fails, like on invalid arguments or when
+/// Jenkins can not be reached, above the codes of build results
+pub const ERROR: i32 = 10;
+
+/// Outcome of a command, mapped to the exit code of the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Success,
+    Unstable,
+    Failure,
+    Aborted,
+    NotBuilt,
+    Timeout,
+    /// the build has no result yet
+    Running,
+    /// jencli failed, always exiting with `ERROR`
+    Error,
+}
+
+impl From<BuildStatus> for Outcome {
+    fn from(status: BuildStatus) -> Self {
+        match status {
+            BuildStatus::Success => Outcome::Success,
+            BuildStatus::Unstable => Outcome::Unstable,
+            BuildStatus::Failure => Outcome::Failure,
+            BuildStatus::Aborted => Outcome::Aborted,
+            BuildStatus::NotBuilt => Outcome::NotBuilt,
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "SUCCESS" => Ok(Outcome::Success),
+            "UNSTABLE" => Ok(Outcome::Unstable),
+            "FAILURE" => Ok(Outcome::Failure),
+            "ABORTED" => Ok(Outcome::Aborted),
+            "NOT_BUILT" => Ok(Outcome::NotBuilt),
+            "TIMEOUT" => Ok(Outcome::Timeout),
+            "RUNNING" => Ok(Outcome::Running),
+            _ => Err(format!(
+                "unknown result {}, expected one of SUCCESS, UNSTABLE, FAILURE, ABORTED, NOT_BUILT, TIMEOUT, RUNNING",
+                s
+            )),
+        }
+    }
+}
+
+pub fn parse_exit_code(s: &str) -> Result<(Outcome, i32), String> {
+    let pos = s
+        .find('=')
+        .ok_or_else(|| format!("invalid RESULT=CODE: no `=` found in `{}`", s))?;
+    let code = s[pos + 1..]
+        .parse()
+        .map_err(|err| format!("invalid exit code in `{}`: {}", s, err))?;
+    Ok((s[..pos].parse()?, code))
+}
+
+pub struct ExitCodes(BTreeMap<Outcome, i32>);
+
+impl ExitCodes {
+    pub fn new(overrides: Vec<(Outcome, i32)>) -> Self {
+        let mut codes = BTreeMap::new();
+        codes.insert(Outcome::Success, 0);
+        codes.insert(Outcome::Failure, 1);
+        codes.insert(Outcome::Unstable, 2);
+        codes.insert(Outcome::Aborted, 3);
+        codes.insert(Outcome::NotBuilt, 4);
+        codes.insert(Outcome::Timeout, 5);
+        codes.insert(Outcome::Running, 6);
+        codes.extend(overrides);
+        codes.insert(Outcome::Error, ERROR);
+        ExitCodes(codes)
+    }
+
+    pub fn code(&self, outcome: Outcome) -> i32 {
+        self.0.get(&outcome).cloned().unwrap_or(0)
+    }
+}
+
+/// Last outcome seen while rendering a command, shared with the iterators
+/// producing the output
+#[derive(Debug, Clone, Default)]
+pub struct OutcomeTracker(Rc<Cell<Option<Outcome>>>);
+
+impl OutcomeTracker {
+    pub fn record(&self, outcome: Option<Outcome>) {
+        self.0.set(outcome);
+    }
+
+    pub fn record_build(&self, status: Option<BuildStatus>) {
+        self.record(status.map(Outcome::from));
+    }
+
+    pub fn get(&self) -> Option<Outcome> {
+        self.0.get()
+    }
+}
@@ -26,6 +26,13 @@ handlebars_helper!(colored_status: |status: str| {
         "FAILURE" => Colour::Red.paint("FAILURE").to_string(),
         "ABORTED" => Colour::White.dimmed().paint("ABORTED").to_string(),
         "NOT_BUILT" => Colour::White.dimmed().paint("NOT_BUILT").to_string(),
+        "PASSED" => Colour::Blue.paint("PASSED").to_string(),
+        "FIXED" => Colour::Blue.paint("FIXED").to_string(),
+        "SKIPPED" => Colour::White.dimmed().paint("SKIPPED").to_string(),
+        "FAILED" => Colour::Red.paint("FAILED").to_string(),
+        "REGRESSION" => Colour::Red.bold().paint("REGRESSION").to_string(),
         x => x.to_string(),
     }
 });
+
+handlebars_helper!(len: |list: array| list.len());
@@ -0,0 +1,305 @@
+use jenkins_api::build::{BuildStatus, CommonBuild};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Criteria to select builds from the history of a job
+#[derive(Debug, Default)]
+pub struct BuildFilter {
+    /// keep builds with one of these results, all builds if empty
+    pub results: Vec<BuildStatus>,
+    /// keep builds started after this timestamp, in milliseconds
+    pub since: Option<u64>,
+    /// keep builds started before this timestamp, in milliseconds
+    pub until: Option<u64>,
+    /// keep builds started by this user, matching their id or their name
+    pub user: Option<String>,
+    /// keep builds of this branch
+    pub branch: Option<String>,
+}
+
+impl BuildFilter {
+    pub fn matches(&self, build: &CommonBuild) -> bool {
+        if !self.results.is_empty()
+            && !build
+                .result
+                .map(|result| self.results.contains(&result))
+                .unwrap_or(false)
+        {
+            return false;
+        }
+        if self
+            .since
+            .map(|since| build.timestamp < since)
+            .unwrap_or(false)
+            || self
+                .until
+                .map(|until| build.timestamp > until)
+                .unwrap_or(false)
+        {
+            return false;
+        }
+
+        let actions = serde_json::to_value(&build.actions).unwrap_or(Value::Null);
+        if let Some(ref user) = self.user {
+            if !started_by(&actions, user) {
+                return false;
+            }
+        }
+        if let Some(ref branch) = self.branch {
+            if !built_branch(&actions, branch) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn actions_field<'a>(actions: &'a Value, field: &'a str) -> impl Iterator<Item = &'a Value> {
+    actions
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(move |action| action.get(field))
+}
+
+fn started_by(actions: &Value, user: &str) -> bool {
+    actions_field(actions, "causes")
+        .filter_map(Value::as_array)
+        .flatten()
+        .any(|cause| {
+            cause.get("userId").and_then(Value::as_str) == Some(user)
+                || cause.get("userName").and_then(Value::as_str) == Some(user)
+        })
+}
+
+fn built_branch(actions: &Value, branch: &str) -> bool {
+    actions_field(actions, "lastBuiltRevision")
+        .filter_map(|revision| revision.get("branch"))
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(|built| built.get("name").and_then(Value::as_str))
+        .any(|name| name == branch || name.ends_with(&format!("/{}", branch)))
+}
+
+/// Statistics over the finished builds of a window of history
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildStats {
+    pub builds: usize,
+    pub successes: usize,
+    /// percentage of successful builds
+    pub success_rate: f64,
+    /// durations in milliseconds
+    pub mean_duration: i64,
+    pub p50_duration: i64,
+    pub p95_duration: i64,
+    /// percentage of builds whose success differs from the previous build
+    pub flakiness: f64,
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        (count as f64 * 1000.0 / total as f64).round() / 10.0
+    }
+}
+
+fn percentile(sorted: &[i64], percent: usize) -> i64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (percent * sorted.len() + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+impl BuildStats {
+    /// Compute statistics from builds sorted from the most recent one, as
+    /// returned by Jenkins. Builds still running are ignored.
+    pub fn from_builds<'a, T>(builds: T) -> Self
+    where
+        T: IntoIterator<Item = &'a CommonBuild>,
+    {
+        let finished = builds
+            .into_iter()
+            .filter(|build| build.result.is_some())
+            .collect::<Vec<_>>();
+        if finished.is_empty() {
+            return BuildStats::default();
+        }
+
+        let successes = finished
+            .iter()
+            .map(|build| build.result == Some(BuildStatus::Success))
+            .collect::<Vec<_>>();
+        let success_count = successes.iter().filter(|success| **success).count();
+        let flips = successes
+            .windows(2)
+            .filter(|pair| pair[0] != pair[1])
+            .count();
+
+        let mut durations = finished
+            .iter()
+            .map(|build| build.duration)
+            .collect::<Vec<_>>();
+        durations.sort_unstable();
+
+        BuildStats {
+            builds: finished.len(),
+            successes: success_count,
+            success_rate: percentage(success_count, finished.len()),
+            mean_duration: durations.iter().sum::<i64>() / durations.len() as i64,
+            p50_duration: percentile(&durations, 50),
+            p95_duration: percentile(&durations, 95),
+            flakiness: percentage(flips, finished.len() - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Finished build started at `timestamp` by `user` on `branch`
+    fn build(
+        result: Option<BuildStatus>,
+        duration: i64,
+        timestamp: u64,
+        user: &str,
+        branch: &str,
+    ) -> CommonBuild {
+        serde_json::from_value(json!({
+            "url": "http://jenkins/job/app/1/",
+            "number": 1,
+            "duration": duration,
+            "estimatedDuration": duration,
+            "timestamp": timestamp,
+            "keepLog": false,
+            "result": result,
+            "displayName": "#1",
+            "building": result.is_none(),
+            "id": "1",
+            "queueId": 1,
+            "actions": [
+                {
+                    "_class": "hudson.model.CauseAction",
+                    "causes": [{ "userId": user, "userName": user.to_uppercase() }],
+                },
+                {},
+                {
+                    "_class": "hudson.plugins.git.util.BuildData",
+                    "lastBuiltRevision": { "branch": [{ "name": format!("origin/{}", branch) }] },
+                },
+            ],
+            "artifacts": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn filter_matches_results_dates_users_and_branches() {
+        let build = build(Some(BuildStatus::Failure), 10, 1000, "alice", "main");
+        assert!(BuildFilter::default().matches(&build));
+
+        let filter = |filter: BuildFilter| filter.matches(&build);
+        assert!(filter(BuildFilter {
+            results: vec![BuildStatus::Success, BuildStatus::Failure],
+            ..BuildFilter::default()
+        }));
+        assert!(!filter(BuildFilter {
+            results: vec![BuildStatus::Success],
+            ..BuildFilter::default()
+        }));
+        assert!(filter(BuildFilter {
+            since: Some(1000),
+            until: Some(1000),
+            ..BuildFilter::default()
+        }));
+        assert!(!filter(BuildFilter {
+            since: Some(1001),
+            ..BuildFilter::default()
+        }));
+        assert!(!filter(BuildFilter {
+            until: Some(999),
+            ..BuildFilter::default()
+        }));
+        assert!(filter(BuildFilter {
+            user: Some("alice".to_string()),
+            ..BuildFilter::default()
+        }));
+        assert!(filter(BuildFilter {
+            user: Some("ALICE".to_string()),
+            ..BuildFilter::default()
+        }));
+        assert!(!filter(BuildFilter {
+            user: Some("bob".to_string()),
+            ..BuildFilter::default()
+        }));
+        assert!(filter(BuildFilter {
+            branch: Some("main".to_string()),
+            ..BuildFilter::default()
+        }));
+        assert!(filter(BuildFilter {
+            branch: Some("origin/main".to_string()),
+            ..BuildFilter::default()
+        }));
+        assert!(!filter(BuildFilter {
+            branch: Some("ain".to_string()),
+            ..BuildFilter::default()
+        }));
+    }
+
+    #[test]
+    fn running_builds_match_no_result() {
+        let build = build(None, 0, 1000, "alice", "main");
+        assert!(!BuildFilter {
+            results: vec![BuildStatus::Success],
+            ..BuildFilter::default()
+        }
+        .matches(&build));
+    }
+
+    #[test]
+    fn stats_over_finished_builds() {
+        let results = [
+            None,
+            Some(BuildStatus::Success),
+            Some(BuildStatus::Failure),
+            Some(BuildStatus::Success),
+            Some(BuildStatus::Success),
+            Some(BuildStatus::Unstable),
+        ];
+        let builds = results
+            .iter()
+            .enumerate()
+            .map(|(i, result)| build(*result, 100 * i as i64, 0, "alice", "main"))
+            .collect::<Vec<_>>();
+
+        let stats = BuildStats::from_builds(&builds);
+        assert_eq!(stats.builds, 5);
+        assert_eq!(stats.successes, 3);
+        assert_eq!(stats.success_rate, 60.0);
+        assert_eq!(stats.mean_duration, 300);
+        assert_eq!(stats.p50_duration, 300);
+        assert_eq!(stats.p95_duration, 500);
+        // success, failure, success, success, unstable: 3 flips out of 4
+        assert_eq!(stats.flakiness, 75.0);
+    }
+
+    #[test]
+    fn stats_without_finished_builds() {
+        let builds = vec![build(None, 0, 0, "alice", "main")];
+        let stats = BuildStats::from_builds(&builds);
+        assert_eq!(stats.builds, 0);
+        assert_eq!(stats.success_rate, 0.0);
+        assert_eq!(stats.flakiness, 0.0);
+
+        let builds = vec![build(Some(BuildStatus::Success), 42, 0, "alice", "main")];
+        let stats = BuildStats::from_builds(&builds);
+        assert_eq!(stats.success_rate, 100.0);
+        assert_eq!(stats.p50_duration, 42);
+        assert_eq!(stats.p95_duration, 42);
+        assert_eq!(stats.flakiness, 0.0);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::Error;
+
+/// A difference between two `config.xml` of a job
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigChange {
+    /// path of the element or attribute, like `/project/builders/hudson.tasks.Shell/command`
+    pub path: String,
+    /// value in the current configuration, `None` if it is added
+    pub old: Option<String>,
+    /// value in the new configuration, `None` if it is removed
+    pub new: Option<String>,
+}
+
+/// Result of pushing a `config.xml` to Jenkins
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigPush {
+    pub name: String,
+    /// the job did not exist and was created
+    pub created: bool,
+    /// nothing was sent to Jenkins
+    pub dry_run: bool,
+    pub changes: Vec<ConfigChange>,
+}
+
+/// Differences between two `config.xml`, ignoring formatting, comments and
+/// the order of attributes. Changes are listed in the order of the documents.
+pub fn diff(old: &str, new: &str) -> Result<Vec<ConfigChange>, Error> {
+    let old = leaves(old)?;
+    let new = leaves(new)?;
+    let old_values = old.iter().cloned().collect::<HashMap<_, _>>();
+    let new_values = new.iter().cloned().collect::<HashMap<_, _>>();
+
+    let removed = |path: &String, value: &String| ConfigChange {
+        path: path.clone(),
+        old: Some(value.clone()),
+        new: None,
+    };
+
+    let mut changes = vec![];
+    // values added since the last value found in both configurations, listed
+    // after the values removed in their place
+    let mut added = vec![];
+    let mut old_leaves = old.iter();
+    for (path, value) in &new {
+        match old_values.get(path) {
+            Some(old_value) => {
+                // values removed before this one in the current configuration
+                for (old_path, old_value) in old_leaves.by_ref() {
+                    if old_path == path {
+                        break;
+                    }
+                    if !new_values.contains_key(old_path) {
+                        changes.push(removed(old_path, old_value));
+                    }
+                }
+                changes.append(&mut added);
+                if old_value != value {
+                    changes.push(ConfigChange {
+                        path: path.clone(),
+                        old: Some(old_value.clone()),
+                        new: Some(value.clone()),
+                    });
+                }
+            }
+            None => added.push(ConfigChange {
+                path: path.clone(),
+                old: None,
+                new: Some(value.clone()),
+            }),
+        }
+    }
+    changes.extend(
+        old_leaves
+            .filter(|(path, _)| !new_values.contains_key(path))
+            .map(|(path, value)| removed(path, value)),
+    );
+    changes.append(&mut added);
+
+    Ok(changes)
+}
+
+/// Values of the `config.xml` of a new job, all added
+pub(crate) fn additions(config: &str) -> Result<Vec<ConfigChange>, Error> {
+    Ok(leaves(config)?
+        .into_iter()
+        .map(|(path, value)| ConfigChange {
+            path,
+            old: None,
+            new: Some(value),
+        })
+        .collect())
+}
+
+/// Values of a document as (path, value), for attributes and for elements
+/// without child elements, in the order of the document
+fn leaves(xml: &str) -> Result<Vec<(String, String)>, Error> {
+    let document =
+        roxmltree::Document::parse(xml).map_err(|err| Error::InvalidConfig(err.to_string()))?;
+    let mut leaves = vec![];
+    collect_leaves(document.root_element(), String::new(), &mut leaves);
+    Ok(leaves)
+}
+
+fn collect_leaves(node: roxmltree::Node, parent: String, leaves: &mut Vec<(String, String)>) {
+    let name = node.tag_name().name();
+    let same_name = node
+        .parent()
+        .map(|parent| {
+            parent
+                .children()
+                .filter(|sibling| sibling.is_element() && sibling.tag_name().name() == name)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let path = match same_name.iter().position(|sibling| *sibling == node) {
+        Some(index) if same_name.len() > 1 => format!("{}/{}[{}]", parent, name, index + 1),
+        _ => format!("{}/{}", parent, name),
+    };
+
+    let mut attributes = node.attributes().iter().collect::<Vec<_>>();
+    attributes.sort_by_key(|attribute| attribute.name());
+    for attribute in attributes {
+        leaves.push((
+            format!("{}/@{}", path, attribute.name()),
+            attribute.value().to_string(),
+        ));
+    }
+
+    let children = node
+        .children()
+        .filter(|child| child.is_element())
+        .collect::<Vec<_>>();
+    if children.is_empty() {
+        let text = node
+            .children()
+            .filter(|child| child.is_text())
+            .filter_map(|child| child.text())
+            .collect::<String>();
+        leaves.push((path, text.trim().to_string()));
+    } else {
+        for child in children {
+            collect_leaves(child, path.clone(), leaves);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(old: &str, new: &str) -> Vec<(String, Option<String>, Option<String>)> {
+        diff(old, new)
+            .unwrap()
+            .into_iter()
+            .map(|change| (change.path, change.old, change.new))
+            .collect()
+    }
+
+    fn change(
+        path: &str,
+        old: Option<&str>,
+        new: Option<&str>,
+    ) -> (String, Option<String>, Option<String>) {
+        (
+            path.to_string(),
+            old.map(ToString::to_string),
+            new.map(ToString::to_string),
+        )
+    }
+
+    #[test]
+    fn formatting_comments_and_attribute_order_are_ignored() {
+        let old = r#"<project a="1" b="2"><description>app</description><disabled>false</disabled></project>"#;
+        let new = r#"<?xml version="1.1"?>
+<project b="2" a="1">
+  <!-- built on every push -->
+  <description>
+    app
+  </description>
+  <disabled>false</disabled>
+</project>"#;
+        assert_eq!(changes(old, new), vec![]);
+    }
+
+    #[test]
+    fn changes_are_listed_in_document_order() {
+        let old = r#"<project>
+  <description>app</description>
+  <keepDependencies>false</keepDependencies>
+  <builders>
+    <hudson.tasks.Shell><command>make</command></hudson.tasks.Shell>
+  </builders>
+  <disabled>false</disabled>
+</project>"#;
+        let new = r#"<project>
+  <description>the app</description>
+  <builders>
+    <hudson.tasks.Shell><command>make</command></hudson.tasks.Shell>
+    <hudson.tasks.Shell><command>make test</command></hudson.tasks.Shell>
+  </builders>
+  <disabled plugin="core">false</disabled>
+</project>"#;
+        assert_eq!(
+            changes(old, new),
+            vec![
+                change("/project/description", Some("app"), Some("the app")),
+                change("/project/keepDependencies", Some("false"), None),
+                change(
+                    "/project/builders/hudson.tasks.Shell/command",
+                    Some("make"),
+                    None
+                ),
+                change(
+                    "/project/builders/hudson.tasks.Shell[1]/command",
+                    None,
+                    Some("make")
+                ),
+                change(
+                    "/project/builders/hudson.tasks.Shell[2]/command",
+                    None,
+                    Some("make test")
+                ),
+                change("/project/disabled/@plugin", None, Some("core")),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected() {
+        assert!(matches!(
+            diff("<project><description></project>", "<project/>"),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert_eq!(
+            additions("<project><disabled>true</disabled></project>")
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -1,75 +1,826 @@
-use serde::Serialize;
+use std::time;
+
+use serde::{Deserialize, Serialize};
 
 use regex::Regex;
 
+pub mod artifacts;
+mod console;
+mod error;
+pub mod history;
+pub mod job_config;
+#[cfg(feature = "test-support")]
+pub mod mock;
+pub mod node;
+pub mod parameters;
+mod raw_client;
+pub mod stages;
+pub mod test_report;
+
+pub use error::Error;
+
+use parameters::{BuildParameters, ParameterDefinition, ParameterValue};
+use raw_client::RawClient;
+
+/// Connection to a Jenkins server, reused by all calls made with it. Clones
+/// share the same connections.
 #[derive(Clone)]
 pub struct JenkinsInformation {
     pub url: String,
-    pub user: Option<String>,
-    pub password: Option<String>,
     pub depth: u8,
+    client: RawClient,
+}
+
+impl JenkinsInformation {
+    pub fn new(
+        url: &str,
+        user: Option<String>,
+        password: Option<String>,
+        depth: u8,
+    ) -> Result<Self, Error> {
+        Ok(JenkinsInformation {
+            url: url.to_string(),
+            depth,
+            client: RawClient::new(url, user, password)?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct JobTree {
+    #[serde(default)]
+    jobs: Vec<JobTreeItem>,
+}
+#[derive(Deserialize)]
+struct JobTreeItem {
+    #[serde(flatten)]
+    job: jenkins_api::job::ShortJob,
+    jobs: Option<Vec<JobTreeItem>>,
+}
+
+fn job_tree_query(max_depth: u8) -> jenkins_api::client::TreeBuilder {
+    use jenkins_api::client::TreeBuilder;
+
+    let jobs = TreeBuilder::object("jobs")
+        .with_subfield("name")
+        .with_subfield("url")
+        .with_subfield("color");
+    if max_depth > 1 {
+        jobs.with_subfield(job_tree_query(max_depth - 1))
+    } else {
+        jobs
+    }
+}
+
+fn flatten_job_tree(
+    prefix: &str,
+    items: Vec<JobTreeItem>,
+) -> Box<dyn Iterator<Item = jenkins_api::job::ShortJob>> {
+    let prefix = prefix.to_string();
+    Box::new(items.into_iter().flat_map(move |item| {
+        let mut job = item.job;
+        job.name = format!("{}{}", prefix, job.name);
+        let children = flatten_job_tree(&format!("{}/", job.name), item.jobs.unwrap_or_default());
+        std::iter::once(job).chain(children)
+    }))
+}
+
+/// Jobs, including the ones in folders, and views of the Jenkins home page
+#[derive(Debug, Serialize)]
+pub struct Home {
+    pub jobs: Vec<jenkins_api::job::ShortJob>,
+    pub views: Vec<jenkins_api::view::ShortView>,
+}
+
+#[derive(Deserialize)]
+struct HomeTree {
+    #[serde(default)]
+    jobs: Vec<JobTreeItem>,
+    #[serde(default)]
+    views: Vec<jenkins_api::view::ShortView>,
+}
+
+pub fn get_home(jenkins_info: &JenkinsInformation, max_depth: u8) -> Result<Home, Error> {
+    use jenkins_api::client::TreeBuilder;
+
+    let home: HomeTree = jenkins_info.client.get_object(
+        "",
+        TreeBuilder::new()
+            .with_field(job_tree_query(max_depth.max(1)))
+            .with_field(
+                TreeBuilder::object("views")
+                    .with_subfield("name")
+                    .with_subfield("url"),
+            )
+            .build(),
+    )?;
+
+    Ok(Home {
+        jobs: flatten_job_tree("", home.jobs).collect(),
+        views: home.views,
+    })
 }
 
-fn build_jenkins_client(
+pub fn get_current_user(
     jenkins_info: &JenkinsInformation,
-) -> Result<jenkins_api::Jenkins, failure::Error> {
-    let mut jenkins_builder = jenkins_api::JenkinsBuilder::new(&jenkins_info.url);
-    jenkins_builder = match (&jenkins_info.user, &jenkins_info.password) {
-        (Some(ref user), None) => jenkins_builder.with_user(user, None),
-        (Some(ref user), Some(ref password)) => jenkins_builder.with_user(user, Some(password)),
-        (_, _) => jenkins_builder,
-    };
-    Ok(jenkins_builder.with_depth(jenkins_info.depth).build()?)
+) -> Result<jenkins_api::user::ShortUser, Error> {
+    let client = &jenkins_info.client;
+
+    Ok(RawClient::send(client.get("/me/api/json"))?.json()?)
 }
 
 pub fn search_job(
     jenkins_info: &JenkinsInformation,
     pattern: &str,
-) -> Result<impl Iterator<Item = jenkins_api::job::ShortJob>, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+    max_depth: u8,
+) -> Result<impl Iterator<Item = jenkins_api::job::ShortJob>, Error> {
+    use jenkins_api::client::TreeBuilder;
 
-    let re = Regex::new(pattern).unwrap();
+    let re = Regex::new(pattern).map_err(|cause| Error::invalid_pattern(pattern, cause))?;
 
-    Ok(jenkins
-        .get_home()?
-        .jobs
-        .into_iter()
-        .filter(move |job| re.is_match(&job.name)))
+    let tree: JobTree = jenkins_info.client.get_object(
+        "",
+        TreeBuilder::new()
+            .with_field(job_tree_query(max_depth.max(1)))
+            .build(),
+    )?;
+
+    Ok(flatten_job_tree("", tree.jobs).filter(move |job| re.is_match(&job.name)))
 }
 
 pub fn get_job(
     jenkins_info: &JenkinsInformation,
     name: &str,
-) -> Result<jenkins_api::job::CommonJob, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+) -> Result<jenkins_api::job::CommonJob, Error> {
+    jenkins_info.client.get_object(
+        &job_path(name),
+        jenkins_api::client::AdvancedQuery::Depth(jenkins_info.depth),
+    )
+}
+
+pub fn get_job_config(jenkins_info: &JenkinsInformation, name: &str) -> Result<String, Error> {
+    let client = &jenkins_info.client;
 
-    Ok(jenkins.get_job(name)?)
+    Ok(RawClient::send(client.get(&format!("{}/config.xml", job_path(name))))?.text()?)
+}
+
+/// Replace the `config.xml` of a job, or create the job in its folder if it
+/// does not exist. With `dry_run`, only compare it to the current one.
+pub fn push_job_config(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    config: &str,
+    dry_run: bool,
+) -> Result<job_config::ConfigPush, Error> {
+    let client = &jenkins_info.client;
+
+    let (created, changes) = match get_job_config(jenkins_info, name) {
+        Ok(current) => (false, job_config::diff(&current, config)?),
+        Err(Error::NotFound { .. }) => (true, job_config::additions(config)?),
+        Err(err) => return Err(err),
+    };
+
+    if !dry_run {
+        let request = if created {
+            let (folder, job) = split_job_name(name);
+            client
+                .post(&format!("{}/createItem", job_path(folder)))?
+                .query(&[("name", job)])
+        } else {
+            client.post(&format!("{}/config.xml", job_path(name)))?
+        };
+        RawClient::send(
+            request
+                .header(reqwest::header::CONTENT_TYPE, "application/xml")
+                .body(config.to_string()),
+        )?;
+    }
+
+    Ok(job_config::ConfigPush {
+        name: name.to_string(),
+        created,
+        dry_run,
+        changes,
+    })
+}
+
+pub fn set_job_enabled(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    enabled: bool,
+) -> Result<jenkins_api::job::CommonJob, Error> {
+    let client = &jenkins_info.client;
+    let action = if enabled { "enable" } else { "disable" };
+
+    RawClient::send(client.post(&format!("{}/{}", job_path(name), action))?)?;
+    get_job(jenkins_info, name)
+}
+
+/// Create a job with the configuration of another one, possibly in another folder
+pub fn copy_job(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    new_name: &str,
+) -> Result<jenkins_api::job::CommonJob, Error> {
+    let client = &jenkins_info.client;
+    let (folder, job) = split_job_name(new_name);
+
+    RawClient::send(
+        client
+            .post(&format!("{}/createItem", job_path(folder)))?
+            .query(&[
+                ("name", job),
+                ("mode", "copy"),
+                ("from", &format!("/{}", name)),
+            ]),
+    )?;
+    // Jenkins does not build a copied job until its configuration is saved once
+    let config = get_job_config(jenkins_info, new_name)?;
+    RawClient::send(
+        client
+            .post(&format!("{}/config.xml", job_path(new_name)))?
+            .header(reqwest::header::CONTENT_TYPE, "application/xml")
+            .body(config),
+    )?;
+    get_job(jenkins_info, new_name)
+}
+
+/// Rename a job, keeping it in the same folder
+pub fn rename_job(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    new_name: &str,
+) -> Result<jenkins_api::job::CommonJob, Error> {
+    let client = &jenkins_info.client;
+    let (folder, _) = split_job_name(name);
+    let new_name = match split_job_name(new_name) {
+        ("", job) => job,
+        (new_folder, job) if new_folder == folder => job,
+        _ => {
+            return Err(Error::InvalidParameter(format!(
+                "cannot move '{}' to another folder when renaming it to '{}'",
+                name, new_name
+            )))
+        }
+    };
+
+    RawClient::send(
+        client
+            .post(&format!("{}/confirmRename", job_path(name)))?
+            .query(&[("newName", new_name)]),
+    )?;
+    get_job(jenkins_info, &format!("{}/{}", folder, new_name))
+}
+
+/// Delete a job and all its builds, returning the job as it was
+pub fn delete_job(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+) -> Result<jenkins_api::job::CommonJob, Error> {
+    let client = &jenkins_info.client;
+    let job = get_job(jenkins_info, name)?;
+
+    RawClient::send(client.post(&format!("{}/doDelete", job_path(name)))?)?;
+    Ok(job)
 }
 
 pub fn get_build(
     jenkins_info: &JenkinsInformation,
     name: &str,
     number: Option<u32>,
-) -> Result<jenkins_api::build::CommonBuild, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+) -> Result<jenkins_api::build::CommonBuild, Error> {
+    jenkins_info.client.get_object(
+        &build_path(name, number),
+        jenkins_api::client::AdvancedQuery::Depth(jenkins_info.depth),
+    )
+}
+
+pub fn get_test_report(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+) -> Result<test_report::TestReport, Error> {
+    use jenkins_api::client::TreeBuilder;
+
+    jenkins_info.client.get_object(
+        &format!("{}/testReport", build_path(name, number)),
+        TreeBuilder::new()
+            .with_field("failCount")
+            .with_field("passCount")
+            .with_field("skipCount")
+            .with_field(
+                TreeBuilder::object("suites")
+                    .with_subfield("name")
+                    .with_subfield(
+                        TreeBuilder::object("cases")
+                            .with_subfield("className")
+                            .with_subfield("name")
+                            .with_subfield("status")
+                            .with_subfield("duration")
+                            .with_subfield("errorDetails")
+                            .with_subfield("errorStackTrace"),
+                    ),
+            )
+            .build(),
+    )
+}
+
+#[derive(Deserialize)]
+struct JobBuilds {
+    #[serde(default, alias = "allBuilds")]
+    builds: Vec<jenkins_api::build::CommonBuild>,
+}
+
+/// Last `limit` builds of a job, most recent first
+pub fn list_builds(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    limit: u32,
+) -> Result<impl Iterator<Item = jenkins_api::build::CommonBuild>, Error> {
+    // `builds` is capped at 100 builds by Jenkins
+    let field = if limit > 100 { "allBuilds" } else { "builds" };
+    let tree = format!(
+        "{}{{0,{}}}",
+        build_tree_query(field).build().to_string(),
+        limit
+    );
+
+    let builds: JobBuilds = RawClient::send(
+        jenkins_info
+            .client
+            .get(&format!("{}/api/json", job_path(name)))
+            .query(&[("tree", tree)]),
+    )?
+    .json()?;
+
+    Ok(builds.builds.into_iter())
+}
+
+/// Number of the last successful build of a job
+pub fn get_last_successful_build_number(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+) -> Result<u32, Error> {
+    #[derive(Deserialize)]
+    struct BuildNumber {
+        number: u32,
+    }
+
+    let build: BuildNumber = jenkins_info.client.get_object(
+        &format!("{}/lastSuccessfulBuild", job_path(name)),
+        jenkins_api::client::TreeBuilder::new()
+            .with_field("number")
+            .build(),
+    )?;
+    Ok(build.number)
+}
+
+/// Artifacts of a build, with the MD5 of the fingerprinted ones. Jenkins does
+/// not give the sizes of artifacts in its API, with `sizes` they are fetched
+/// with a HEAD request for each artifact.
+pub fn list_artifacts(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    sizes: bool,
+) -> Result<impl Iterator<Item = artifacts::BuildArtifact>, Error> {
+    use jenkins_api::client::TreeBuilder;
+
+    let build: artifacts::BuildArtifacts = jenkins_info.client.get_object(
+        &build_path(name, number),
+        TreeBuilder::new()
+            .with_field("url")
+            .with_field(
+                TreeBuilder::object("artifacts")
+                    .with_subfield("displayPath")
+                    .with_subfield("fileName")
+                    .with_subfield("relativePath"),
+            )
+            .with_field(
+                TreeBuilder::object("fingerprint")
+                    .with_subfield("fileName")
+                    .with_subfield("hash"),
+            )
+            .build(),
+    )?;
+
+    let list = build
+        .artifacts
+        .iter()
+        .map(|artifact| {
+            let url = format!(
+                "{}/artifact/{}",
+                build.url.trim_end_matches('/'),
+                artifact
+                    .relative_path
+                    .split('/')
+                    .map(urlencoding::encode)
+                    .collect::<Vec<_>>()
+                    .join("/")
+            );
+            let size = if sizes {
+                RawClient::send(jenkins_info.client.head(&url_path(jenkins_info, &url)?))?
+                    .headers()
+                    .get(reqwest::header::CONTENT_LENGTH)
+                    .and_then(|length| length.to_str().ok())
+                    .and_then(|length| length.parse().ok())
+            } else {
+                None
+            };
+            Ok(artifacts::BuildArtifact {
+                file_name: artifact.file_name.clone(),
+                relative_path: artifact.relative_path.clone(),
+                url,
+                size,
+                md5: build.md5_of(artifact),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(list.into_iter())
+}
+
+/// Download an artifact in `dest`, resuming a previous partial download and
+/// checking its MD5 when known. A file not matching its MD5 is removed.
+pub fn download_artifact(
+    jenkins_info: &JenkinsInformation,
+    artifact: &artifacts::BuildArtifact,
+    dest: &std::path::Path,
+) -> Result<artifacts::DownloadedArtifact, Error> {
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, BufWriter};
+
+    use reqwest::{header, StatusCode};
+
+    let path = artifact.destination(dest);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let existing = fs::metadata(&path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    let complete = artifact.size == Some(existing);
+    let mut downloaded = 0;
+    if !complete {
+        let mut request = jenkins_info
+            .client
+            .get(&url_path(jenkins_info, &artifact.url)?);
+        if existing > 0 {
+            request = request.header(header::RANGE, format!("bytes={}-", existing));
+        }
+        let response = request.send()?;
+        log::debug!("{} {}", response.status(), response.url());
+        // the partial file is already complete, Jenkins has nothing more to send
+        if response.status() != StatusCode::RANGE_NOT_SATISFIABLE {
+            let mut response = response.error_for_status()?;
+            let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resumed)
+                .truncate(!resumed)
+                .open(&path)?;
+            downloaded = io::copy(&mut response, &mut BufWriter::new(file))?;
+        }
+    }
 
+    let verified = match artifact.md5 {
+        Some(ref expected) => {
+            let actual = artifacts::md5_of_file(&path)?;
+            if &actual != expected {
+                fs::remove_file(&path)?;
+                return Err(Error::ChecksumMismatch {
+                    path: path.display().to_string(),
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+            true
+        }
+        None => false,
+    };
+
+    Ok(artifacts::DownloadedArtifact {
+        relative_path: artifact.relative_path.clone(),
+        path: path.display().to_string(),
+        size: fs::metadata(&path)?.len(),
+        downloaded,
+        verified,
+    })
+}
+
+/// Path of a job from its name, with `/` separating folders
+fn job_path(name: &str) -> String {
+    name.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| format!("/job/{}", urlencoding::encode(segment)))
+        .collect()
+}
+
+/// Folder containing a job and the name of the job in this folder
+fn split_job_name(name: &str) -> (&str, &str) {
+    match name.rfind('/') {
+        Some(pos) => (&name[..pos], &name[pos + 1..]),
+        None => ("", name),
+    }
+}
+
+/// Path of a view from its name, prefixed with the folder containing it if any
+fn view_path(name: &str) -> String {
+    match name.rfind('/') {
+        Some(pos) => format!(
+            "{}/view/{}",
+            job_path(&name[..pos]),
+            urlencoding::encode(&name[pos + 1..])
+        ),
+        None => format!("/view/{}", urlencoding::encode(name)),
+    }
+}
+
+fn build_path(name: &str, number: Option<u32>) -> String {
     match number {
-        Some(n) => Ok(jenkins.get_build(name, n)?),
-        None => Ok(jenkins.get_build(name, "lastBuild")?),
+        Some(n) => format!("{}/{}", job_path(name), n),
+        None => format!("{}/lastBuild", job_path(name)),
+    }
+}
+
+/// Path of an object relative to the Jenkins URL, from its absolute URL as
+/// linked by Jenkins. A Jenkins served under a context path, like `/jenkins`,
+/// links to `/jenkins/queue/item/5`, while the context path is already part of
+/// the configured URL.
+fn url_path(jenkins_info: &JenkinsInformation, url: &str) -> Result<String, Error> {
+    let base = reqwest::Url::parse(&jenkins_info.url)?;
+    let context_path = base.path().trim_end_matches('/');
+    let url = reqwest::Url::parse(url)?;
+    let path = match url.path().strip_prefix(context_path) {
+        Some(path) if path.is_empty() || path.starts_with('/') => path,
+        _ => url.path(),
+    };
+    Ok(path.trim_end_matches('/').to_string())
+}
+
+fn resolve_build_number(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+) -> Result<u32, Error> {
+    match number {
+        Some(n) => Ok(n),
+        None => Ok(get_build(jenkins_info, name, None)?.number),
+    }
+}
+
+pub fn get_console(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+) -> Result<String, Error> {
+    let client = &jenkins_info.client;
+
+    Ok(
+        RawClient::send(client.get(&format!("{}/consoleText", build_path(name, number))))?
+            .text()?,
+    )
+}
+
+pub fn follow_console(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    polling: time::Duration,
+) -> Result<impl Iterator<Item = Result<String, Error>>, Error> {
+    follow_console_until(jenkins_info, name, number, polling, || false)
+}
+
+/// Like `follow_console`, but stops following once `stop` returns true, as
+/// checked while waiting for new output
+pub fn follow_console_until<F>(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    polling: time::Duration,
+    stop: F,
+) -> Result<impl Iterator<Item = Result<String, Error>>, Error>
+where
+    F: FnMut() -> bool + 'static,
+{
+    let number = resolve_build_number(jenkins_info, name, number)?;
+
+    Ok(console::ConsoleFollower::new(
+        jenkins_info.client.clone(),
+        build_path(name, Some(number)),
+        polling,
+        Box::new(stop),
+    ))
+}
+
+pub fn get_pipeline_run(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+) -> Result<stages::PipelineRun, Error> {
+    let client = &jenkins_info.client;
+
+    Ok(
+        RawClient::send(client.get(&format!("{}/wfapi/describe", build_path(name, number))))?
+            .json()?,
+    )
+}
+
+/// Console output of the steps of a stage of a Pipeline run, found by name or id
+pub fn get_stage_log(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    stage: &str,
+) -> Result<String, Error> {
+    let client = &jenkins_info.client;
+    let number = resolve_build_number(jenkins_info, name, number)?;
+    let path = build_path(name, Some(number));
+
+    let run = get_pipeline_run(jenkins_info, name, Some(number))?;
+    let stage = run
+        .stages
+        .iter()
+        .find(|candidate| candidate.name == stage || candidate.id == stage)
+        .ok_or_else(|| {
+            Error::InvalidParameter(format!(
+                "no stage '{}' in {} #{}, stages are: {}",
+                stage,
+                name,
+                number,
+                run.stages
+                    .iter()
+                    .map(|stage| stage.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?;
+
+    let description: stages::StageDescription = RawClient::send(client.get(&format!(
+        "{}/execution/node/{}/wfapi/describe",
+        path, stage.id
+    )))?
+    .json()?;
+    let mut log = String::new();
+    for node in description
+        .stage_flow_nodes
+        .iter()
+        .filter(|node| node.has_log())
+    {
+        let node_log: stages::FlowNodeLog =
+            RawClient::send(client.get(&format!("{}/execution/node/{}/wfapi/log", path, node.id)))?
+                .json()?;
+        log += &stages::html_to_text(&node_log.text);
+    }
+    Ok(log)
+}
+
+/// `input` steps of a Pipeline run waiting for someone to proceed or abort
+pub fn get_pending_inputs(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+) -> Result<Vec<stages::InputAction>, Error> {
+    let client = &jenkins_info.client;
+
+    let pending: Vec<stages::PendingInputAction> = RawClient::send(client.get(&format!(
+        "{}/wfapi/pendingInputActions",
+        build_path(name, number)
+    )))?
+    .json()?;
+    Ok(pending.into_iter().map(stages::InputAction::from).collect())
+}
+
+/// Proceed with or abort an `input` step of a Pipeline run. The input can be
+/// omitted when only one is pending, and parameters not given keep their
+/// default value.
+pub fn answer_input(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    id: Option<&str>,
+    proceed: bool,
+    parameters: &std::collections::BTreeMap<String, String>,
+) -> Result<stages::InputAction, Error> {
+    let client = &jenkins_info.client;
+    let number = resolve_build_number(jenkins_info, name, number)?;
+    let path = build_path(name, Some(number));
+
+    let mut pending = get_pending_inputs(jenkins_info, name, Some(number))?;
+    let ids = pending
+        .iter()
+        .map(|input| input.id.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let position = match id {
+        Some(id) => pending
+            .iter()
+            .position(|input| input.id == id)
+            .ok_or_else(|| {
+                Error::InvalidParameter(format!(
+                    "no input '{}' pending in {} #{}, inputs are: {}",
+                    id, name, number, ids
+                ))
+            })?,
+        None if pending.len() == 1 => 0,
+        None if pending.is_empty() => {
+            return Err(Error::InvalidParameter(format!(
+                "no input pending in {} #{}",
+                name, number
+            )))
+        }
+        None => {
+            return Err(Error::InvalidParameter(format!(
+                "several inputs pending in {} #{}, choose one with --id: {}",
+                name, number, ids
+            )))
+        }
+    };
+    let mut input = pending.remove(position);
+    if let Some(unknown) = parameters
+        .keys()
+        .find(|key| !input.inputs.iter().any(|parameter| parameter.name == **key))
+    {
+        return Err(Error::InvalidParameter(format!(
+            "input '{}' has no parameter '{}'",
+            input.id, unknown
+        )));
     }
+
+    // resolved first, so that an answer sent is never reported as failing
+    let user = get_current_user(jenkins_info)?.full_name;
+    let request = if !proceed {
+        client.post(&format!("{}/input/{}/abort", path, input.id))?
+    } else if input.inputs.is_empty() {
+        client.post(&format!("{}/input/{}/proceedEmpty", path, input.id))?
+    } else {
+        let values = input
+            .inputs
+            .iter()
+            .map(|parameter| {
+                let value = match parameters.get(&parameter.name) {
+                    Some(value) if parameter.kind == "BooleanParameterDefinition" => {
+                        serde_json::Value::Bool(value == "true")
+                    }
+                    Some(value) => serde_json::Value::String(value.clone()),
+                    None => parameter
+                        .default_value
+                        .clone()
+                        .unwrap_or(serde_json::Value::Null),
+                };
+                serde_json::json!({ "name": parameter.name, "value": value })
+            })
+            .collect::<Vec<_>>();
+        client
+            .post(&format!("{}/input/{}/submit", path, input.id))?
+            .form(&[
+                (
+                    "json",
+                    serde_json::json!({ "parameter": values }).to_string(),
+                ),
+                ("proceed", input.proceed_text.clone()),
+            ])
+    };
+    RawClient::send(request)?;
+
+    input.answer = Some(stages::InputAnswer {
+        proceeded: proceed,
+        parameters: parameters.clone(),
+        user,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    });
+    Ok(input)
+}
+
+#[derive(Deserialize)]
+struct HomeViews {
+    views: Vec<jenkins_api::view::ShortView>,
 }
 
 pub fn list_views(
     jenkins_info: &JenkinsInformation,
     pattern: Option<String>,
-) -> Result<impl Iterator<Item = jenkins_api::view::ShortView>, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+) -> Result<impl Iterator<Item = jenkins_api::view::ShortView>, Error> {
+    use jenkins_api::client::TreeBuilder;
 
-    let views = jenkins.get_home()?.views.into_iter();
+    let home: HomeViews = jenkins_info.client.get_object(
+        "",
+        TreeBuilder::new()
+            .with_field(
+                TreeBuilder::object("views")
+                    .with_subfield("name")
+                    .with_subfield("url"),
+            )
+            .build(),
+    )?;
+    let views = home.views.into_iter();
 
     match pattern {
         Some(pattern) => {
-            let re = Regex::new(&pattern).unwrap();
+            let re =
+                Regex::new(&pattern).map_err(|cause| Error::invalid_pattern(&pattern, cause))?;
             Ok(views
                 .filter(move |view| re.is_match(&view.name))
                 .collect::<Vec<jenkins_api::view::ShortView>>()
@@ -82,45 +833,426 @@ pub fn list_views(
 pub fn list_jobs_of_view(
     jenkins_info: &JenkinsInformation,
     name: &str,
-) -> Result<impl Iterator<Item = jenkins_api::job::ShortJob>, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+) -> Result<impl Iterator<Item = jenkins_api::job::ShortJob>, Error> {
+    let view: jenkins_api::view::CommonView = jenkins_info.client.get_object(
+        &view_path(name),
+        jenkins_api::client::AdvancedQuery::Depth(jenkins_info.depth),
+    )?;
 
-    Ok(jenkins.get_view(name)?.jobs.into_iter())
+    Ok(view.jobs.into_iter())
+}
+
+#[derive(Deserialize)]
+struct JobProperties {
+    property: Vec<JobProperty>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JobProperty {
+    #[serde(default)]
+    parameter_definitions: Vec<ParameterDefinition>,
+}
+
+pub fn get_job_parameters(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+) -> Result<Vec<ParameterDefinition>, Error> {
+    use jenkins_api::client::TreeBuilder;
+
+    let properties: JobProperties = jenkins_info.client.get_object(
+        &job_path(name),
+        TreeBuilder::new()
+            .with_field(
+                TreeBuilder::object("property").with_subfield(
+                    TreeBuilder::object("parameterDefinitions")
+                        .with_subfield("name")
+                        .with_subfield("type")
+                        .with_subfield("description")
+                        .with_subfield("choices")
+                        .with_subfield(
+                            TreeBuilder::object("defaultParameterValue").with_subfield("value"),
+                        ),
+                ),
+            )
+            .build(),
+    )?;
+
+    Ok(properties
+        .property
+        .into_iter()
+        .flat_map(|property| property.parameter_definitions)
+        .collect())
 }
 
 pub fn trigger_job(
     jenkins_info: &JenkinsInformation,
     name: &str,
-) -> Result<jenkins_api::queue::ShortQueueItem, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+    parameters: &BuildParameters,
+) -> Result<jenkins_api::queue::ShortQueueItem, Error> {
+    let client = &jenkins_info.client;
+
+    let has_files = parameters
+        .values()
+        .any(|value| matches!(value, ParameterValue::File(_)));
+    // a parameterized job only builds with its default values through
+    // `buildWithParameters`, `build` answers with the form to fill instead
+    let request = if parameters.is_empty() && get_job_parameters(jenkins_info, name)?.is_empty() {
+        client.post(&format!("{}/build", job_path(name)))?
+    } else if !has_files {
+        let values = parameters
+            .iter()
+            .filter_map(|(key, value)| match value {
+                ParameterValue::Value(value) => Some((key, value)),
+                ParameterValue::File(_) => None,
+            })
+            .collect::<std::collections::BTreeMap<_, _>>();
+        client
+            .post(&format!("{}/buildWithParameters", job_path(name)))?
+            .form(&values)
+    } else {
+        let mut form = reqwest::multipart::Form::new();
+        for (key, value) in parameters {
+            form = match value {
+                ParameterValue::Value(value) => form.text(key.clone(), value.clone()),
+                ParameterValue::File(path) => form.file(key.clone(), path)?,
+            };
+        }
+        client
+            .post(&format!("{}/buildWithParameters", job_path(name)))?
+            .multipart(form)
+    };
+    let response = RawClient::send(request)?;
+
+    match response.headers().get(reqwest::header::LOCATION) {
+        Some(location) => Ok(serde_json::from_value(serde_json::json!({
+            "url": location.to_str()?
+        }))?),
+        None => Err(Error::Other(failure::format_err!(
+            "no queue item returned when triggering '{}'",
+            name
+        ))),
+    }
+}
+
+#[derive(Deserialize)]
+struct BuildActions {
+    actions: Vec<BuildAction>,
+}
+#[derive(Deserialize)]
+struct BuildAction {
+    #[serde(rename = "_class")]
+    class: Option<String>,
+    #[serde(default)]
+    parameters: Vec<BuildParameter>,
+}
+#[derive(Deserialize)]
+struct BuildParameter {
+    name: String,
+    value: Option<serde_json::Value>,
+}
+
+/// Parameters a build was triggered with. Values of file and password
+/// parameters are not sent back by Jenkins and are left out.
+pub fn get_build_parameters(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+) -> Result<BuildParameters, Error> {
+    use jenkins_api::client::TreeBuilder;
+
+    let build: BuildActions = jenkins_info.client.get_object(
+        &build_path(name, number),
+        TreeBuilder::new()
+            .with_field(
+                TreeBuilder::object("actions")
+                    .with_subfield("_class")
+                    .with_subfield(
+                        TreeBuilder::object("parameters")
+                            .with_subfield("name")
+                            .with_subfield("value"),
+                    ),
+            )
+            .build(),
+    )?;
+
+    Ok(build
+        .actions
+        .into_iter()
+        .filter(|action| action.class.as_deref() == Some("hudson.model.ParametersAction"))
+        .flat_map(|action| action.parameters)
+        .filter_map(|parameter| {
+            let value = match parameter.value? {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Null => return None,
+                value => value.to_string(),
+            };
+            Some((parameter.name, ParameterValue::Value(value)))
+        })
+        .collect())
+}
+
+/// Trigger a job again with the parameters of one of its builds, replaced by
+/// `overrides`
+pub fn rebuild_job(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    overrides: BuildParameters,
+) -> Result<jenkins_api::queue::ShortQueueItem, Error> {
+    let mut parameters = get_build_parameters(jenkins_info, name, number)?;
+    parameters.extend(overrides);
+
+    trigger_job(jenkins_info, name, &parameters)
+}
+
+/// Run a Pipeline build again with another main script, keeping its
+/// parameters, and return the queue item of the new build
+pub fn replay_build(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    script: &str,
+) -> Result<jenkins_api::queue::ShortQueueItem, Error> {
+    let client = &jenkins_info.client;
+    let number = resolve_build_number(jenkins_info, name, number)?;
+
+    let build = get_build(jenkins_info, name, Some(number))?;
+    if build.class.as_deref() != Some(stages::PIPELINE_RUN_CLASS) {
+        return Err(Error::InvalidParameter(format!(
+            "{} #{} is not a Pipeline build, only those can be replayed",
+            name, number
+        )));
+    }
+    let next_build_number = get_job(jenkins_info, name)?.next_build_number;
+    let known = get_queue(jenkins_info)?
+        .map(|item| item.id)
+        .collect::<Vec<_>>();
+
+    let response = RawClient::send(
+        client
+            .post(&format!("{}/replay/run", build_path(name, Some(number))))?
+            .form(&[
+                ("mainScript", script.to_string()),
+                (
+                    "json",
+                    serde_json::json!({ "mainScript": script }).to_string(),
+                ),
+            ]),
+    )?;
+    if let Some(location) = response.headers().get(reqwest::header::LOCATION) {
+        if location.to_str()?.contains("/queue/item/") {
+            return Ok(serde_json::from_value(serde_json::json!({
+                "url": location.to_str()?
+            }))?);
+        }
+    }
+
+    // Jenkins usually redirects to the job instead of the queue item: look
+    // for a new item of the job caused by a replay, or for its build if it
+    // already left the queue
+    let queued = get_queue(jenkins_info)?
+        .filter(|item| !known.contains(&item.id))
+        .filter(|item| {
+            job_name_from_url(&item.task.url)
+                .map(|job| job == name)
+                .unwrap_or(false)
+        })
+        .find(|item| is_replay(&item.actions))
+        .map(|item| item.id as i32);
+    let id = match queued {
+        Some(id) => id,
+        None => {
+            let last_build_number = get_job(jenkins_info, name)?
+                .last_build
+                .map(|build| build.number)
+                .unwrap_or(0);
+            let mut started = None;
+            for number in next_build_number..=last_build_number {
+                let build = get_build(jenkins_info, name, Some(number))?;
+                if !known.contains(&(build.queue_id as u32)) && is_replay(&build.actions) {
+                    started = Some(build.queue_id);
+                    break;
+                }
+            }
+            started.ok_or_else(|| {
+                Error::Other(failure::err_msg(format!(
+                    "{} #{} was replayed, but its new build could not be found",
+                    name, number
+                )))
+            })?
+        }
+    };
+    Ok(serde_json::from_value(serde_json::json!({
+        "url": format!("{}/queue/item/{}/", jenkins_info.url.trim_end_matches('/'), id)
+    }))?)
+}
+
+/// Class of the cause of builds started by replaying another one
+const REPLAY_CAUSE_CLASS: &str = "org.jenkinsci.plugins.workflow.cps.replay.ReplayCause";
+
+/// Whether actions of a queue item or a build show it was caused by a replay
+fn is_replay(actions: &[jenkins_api::action::CommonAction]) -> bool {
+    let actions = serde_json::to_value(actions).unwrap_or(serde_json::Value::Null);
+    actions
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|action| action.get("causes"))
+        .filter_map(serde_json::Value::as_array)
+        .flatten()
+        .any(|cause| {
+            cause.get("_class").and_then(serde_json::Value::as_str) == Some(REPLAY_CAUSE_CLASS)
+        })
+}
+
+/// How to stop a running build, each signal being stronger than the previous one
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum StopSignal {
+    Stop,
+    Term,
+    Kill,
+}
+
+impl StopSignal {
+    fn endpoint(self) -> &'static str {
+        match self {
+            StopSignal::Stop => "stop",
+            StopSignal::Term => "term",
+            StopSignal::Kill => "kill",
+        }
+    }
+}
+
+/// Full name of a job from the absolute URL of the job or of one of its builds, with folders separated by `/`
+pub fn job_name_from_url(url: &str) -> Result<String, Error> {
+    let url = reqwest::Url::parse(url)?;
+    let mut segments = url.path().split('/');
+    let mut names = vec![];
+    while let Some(segment) = segments.next() {
+        if segment == "job" {
+            if let Some(name) = segments.next() {
+                names.push(urlencoding::decode(name).map_err(|err| {
+                    Error::Other(failure::format_err!("invalid job URL {}: {:?}", url, err))
+                })?);
+            }
+        }
+    }
+    Ok(names.join("/"))
+}
+
+fn stop_build_at(
+    jenkins_info: &JenkinsInformation,
+    path: &str,
+    signal: StopSignal,
+    grace: time::Duration,
+) -> Result<jenkins_api::build::CommonBuild, Error> {
+    let client = &jenkins_info.client;
+    let get_build = || -> Result<jenkins_api::build::CommonBuild, Error> {
+        client.get_object(
+            path,
+            jenkins_api::client::AdvancedQuery::Depth(jenkins_info.depth),
+        )
+    };
+
+    for step in &[StopSignal::Stop, StopSignal::Term, StopSignal::Kill] {
+        if *step > signal {
+            break;
+        }
+        RawClient::send(client.post(&format!("{}/{}", path, step.endpoint()))?)?;
+
+        let started = time::Instant::now();
+        loop {
+            let build = get_build()?;
+            if !build.building {
+                return Ok(build);
+            }
+            if started.elapsed() >= grace {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_secs(1));
+        }
+    }
+    get_build()
+}
+
+/// Stop a build, escalating up to `signal` if it is still running after `grace`
+pub fn stop_build(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    number: Option<u32>,
+    signal: StopSignal,
+    grace: time::Duration,
+) -> Result<jenkins_api::build::CommonBuild, Error> {
+    let number = resolve_build_number(jenkins_info, name, number)?;
 
-    Ok(jenkins.build_job(name)?)
+    stop_build_at(jenkins_info, &build_path(name, Some(number)), signal, grace)
+}
+
+/// Stop all running builds of jobs whose name match `pattern`. A build that
+/// can not be stopped does not prevent stopping the others, each build comes
+/// with the result of stopping it.
+pub fn stop_matching_builds(
+    jenkins_info: &JenkinsInformation,
+    pattern: &str,
+    signal: StopSignal,
+    grace: time::Duration,
+) -> Result<impl Iterator<Item = Result<jenkins_api::build::CommonBuild, Error>>, Error> {
+    let re = Regex::new(pattern).map_err(|cause| Error::invalid_pattern(pattern, cause))?;
+
+    let mut stopped = vec![];
+    for build in get_executors(jenkins_info)?.filter_map(|executor| executor.build) {
+        // a build whose job name can not be read is reported as failing to stop
+        let matching = job_name_from_url(&build.url).map(|name| re.is_match(&name));
+        if build.building && matching.as_ref().map_or(true, |matching| *matching) {
+            stopped.push(matching.and_then(|_| {
+                stop_build_at(
+                    jenkins_info,
+                    &url_path(jenkins_info, &build.url)?,
+                    signal,
+                    grace,
+                )
+            }));
+        }
+    }
+    Ok(stopped.into_iter())
+}
+
+pub fn cancel_queue_item(jenkins_info: &JenkinsInformation, id: i32) -> Result<(), Error> {
+    let client = &jenkins_info.client;
+
+    RawClient::send(client.post("/queue/cancelItem")?.query(&[("id", id)]))?;
+    Ok(())
 }
 
 pub fn get_queue_item(
     jenkins_info: &JenkinsInformation,
     queue_item: &jenkins_api::queue::ShortQueueItem,
-) -> Result<jenkins_api::queue::QueueItem, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
-
-    Ok(queue_item.get_full_queue_item(&jenkins)?)
+) -> Result<jenkins_api::queue::QueueItem, Error> {
+    jenkins_info.client.get_object(
+        &url_path(jenkins_info, &queue_item.url)?,
+        jenkins_api::client::AdvancedQuery::Depth(jenkins_info.depth),
+    )
 }
 
 pub fn get_queue_item_from_id(
     jenkins_info: &JenkinsInformation,
     id: i32,
-) -> Result<jenkins_api::queue::QueueItem, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
-
-    Ok(jenkins.get_queue_item(id)?)
+) -> Result<jenkins_api::queue::QueueItem, Error> {
+    jenkins_info.client.get_object(
+        &format!("/queue/item/{}", id),
+        jenkins_api::client::AdvancedQuery::Depth(jenkins_info.depth),
+    )
 }
 
 pub fn get_queue(
     jenkins_info: &JenkinsInformation,
-) -> Result<impl Iterator<Item = jenkins_api::queue::QueueItem>, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+) -> Result<impl Iterator<Item = jenkins_api::queue::QueueItem>, Error> {
+    let queue: jenkins_api::queue::Queue = jenkins_info.client.get_object(
+        "/queue",
+        jenkins_api::client::AdvancedQuery::Depth(jenkins_info.depth),
+    )?;
 
-    Ok(jenkins.get_queue()?.items.into_iter())
+    Ok(queue.items.into_iter())
 }
 
 #[derive(Debug, Serialize)]
@@ -130,48 +1262,207 @@ pub struct BuildingOn {
     pub build: Option<jenkins_api::build::CommonBuild>,
 }
 
+#[derive(Deserialize)]
+struct Computers {
+    computer: Vec<ComputerExecutors>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ComputerExecutors {
+    display_name: String,
+    executors: Vec<ExecutorBuild>,
+    /// flyweight executors, running Pipeline builds outside of their `node` steps
+    #[serde(default)]
+    one_off_executors: Vec<ExecutorBuild>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExecutorBuild {
+    current_executable: Option<jenkins_api::build::CommonBuild>,
+    progress: jenkins_api::nodes::computer::ExecutorProgress,
+}
+
+/// Fields of a `CommonBuild` to ask for in a `tree` query
+fn build_tree_query(name: &str) -> jenkins_api::client::TreeBuilder {
+    use jenkins_api::client::TreeBuilder;
+
+    [
+        "_class",
+        "url",
+        "number",
+        "duration",
+        "estimatedDuration",
+        "timestamp",
+        "keepLog",
+        "result",
+        "displayName",
+        "fullDisplayName",
+        "description",
+        "building",
+        "id",
+        "queueId",
+    ]
+    .iter()
+    .fold(TreeBuilder::object(name), |tree, field| {
+        tree.with_subfield(*field)
+    })
+    .with_subfield(
+        TreeBuilder::object("actions")
+            .with_subfield("_class")
+            .with_subfield(
+                TreeBuilder::object("causes")
+                    .with_subfield("shortDescription")
+                    .with_subfield("userId")
+                    .with_subfield("userName"),
+            )
+            .with_subfield(
+                TreeBuilder::object("lastBuiltRevision")
+                    .with_subfield(TreeBuilder::object("branch").with_subfield("name")),
+            ),
+    )
+    .with_subfield(
+        TreeBuilder::object("artifacts")
+            .with_subfield("displayPath")
+            .with_subfield("fileName")
+            .with_subfield("relativePath"),
+    )
+}
+
 pub fn get_executors(
     jenkins_info: &JenkinsInformation,
-) -> Result<impl Iterator<Item = BuildingOn>, failure::Error> {
-    let jenkins = build_jenkins_client(jenkins_info)?;
+) -> Result<impl Iterator<Item = BuildingOn>, Error> {
+    use jenkins_api::client::TreeBuilder;
+    use jenkins_api::nodes::computer::ExecutorProgress;
+
+    let computers: Computers = jenkins_info.client.get_object(
+        "/computer",
+        TreeBuilder::new()
+            .with_field(
+                TreeBuilder::object("computer")
+                    .with_subfield("displayName")
+                    .with_subfield(
+                        TreeBuilder::object("executors")
+                            .with_subfield("progress")
+                            .with_subfield(build_tree_query("currentExecutable")),
+                    )
+                    .with_subfield(
+                        TreeBuilder::object("oneOffExecutors")
+                            .with_subfield("progress")
+                            .with_subfield(build_tree_query("currentExecutable")),
+                    ),
+            )
+            .build(),
+    )?;
 
-    Ok(jenkins
-        .get_nodes()?
-        .computers
+    Ok(computers
+        .computer
         .into_iter()
-        .flat_map(move |computer| {
-            let node_name = computer.display_name.clone();
-            let jenkins = &jenkins;
+        .flat_map(|computer| {
+            let node = computer.display_name;
             computer
                 .executors
-                .iter()
-                .filter_map(move |executor| {
-                    if let jenkins_api::nodes::computer::Executor::Executor {
-                        current_executable,
+                .into_iter()
+                .chain(computer.one_off_executors)
+                .filter_map(move |executor| match executor.progress {
+                    ExecutorProgress::Percent(progress) => Some(BuildingOn {
+                        node: node.clone(),
                         progress,
-                        ..
-                    } = executor
-                    {
-                        let node_name = node_name.clone();
-
-                        if let jenkins_api::nodes::computer::ExecutorProgress::Percent(p) = progress
-                        {
-                            Some(BuildingOn {
-                                node: node_name,
-                                progress: *p,
-                                build: current_executable
-                                    .clone()
-                                    .map(|exec| exec.get_full_build(jenkins).unwrap()),
-                            })
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
+                        build: executor.current_executable,
+                    }),
+                    _ => None,
                 })
-                .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>()
         .into_iter())
 }
+
+pub fn list_nodes(
+    jenkins_info: &JenkinsInformation,
+    pattern: Option<String>,
+) -> Result<impl Iterator<Item = node::Node>, Error> {
+    let computers: node::Computers = jenkins_info.client.get_object(
+        "/computer",
+        jenkins_api::client::TreeBuilder::new()
+            .with_field(node_tree_query("computer"))
+            .build(),
+    )?;
+    let nodes = computers.computer.into_iter().map(node::Node::from);
+
+    let re = match pattern {
+        Some(pattern) => {
+            Some(Regex::new(&pattern).map_err(|cause| Error::invalid_pattern(&pattern, cause))?)
+        }
+        None => None,
+    };
+    Ok(nodes
+        .filter(|node| re.as_ref().map_or(true, |re| re.is_match(&node.name)))
+        .collect::<Vec<_>>()
+        .into_iter())
+}
+
+pub fn get_node(jenkins_info: &JenkinsInformation, name: &str) -> Result<node::Node, Error> {
+    list_nodes(jenkins_info, None)?
+        .find(|node| node.name == name || node.url_name == name)
+        .ok_or_else(|| Error::NotFound {
+            url: format!("{}/computer/{}", jenkins_info.url, name),
+        })
+}
+
+/// Mark a node temporarily offline with a reason, or bring it back online. A
+/// node that is already offline only gets its reason updated, and a node
+/// disconnected from Jenkins is not reconnected.
+pub fn set_node_offline(
+    jenkins_info: &JenkinsInformation,
+    name: &str,
+    offline: bool,
+    reason: Option<&str>,
+) -> Result<node::Node, Error> {
+    let client = &jenkins_info.client;
+    let node = get_node(jenkins_info, name)?;
+    let path = format!("/computer/{}", urlencoding::encode(&node.url_name));
+    let message = [("offlineMessage", reason.unwrap_or(""))];
+
+    if node.temporarily_offline != offline {
+        RawClient::send(
+            client
+                .post(&format!("{}/toggleOffline", path))?
+                .query(&message),
+        )?;
+    } else if offline && reason.is_some() {
+        RawClient::send(
+            client
+                .post(&format!("{}/changeOfflineCause", path))?
+                .query(&message),
+        )?;
+    }
+
+    get_node(jenkins_info, &node.url_name)
+}
+
+/// Fields of a `Computer` to ask for in a `tree` query
+fn node_tree_query(name: &str) -> jenkins_api::client::TreeBuilder {
+    use jenkins_api::client::TreeBuilder;
+
+    [
+        "_class",
+        "displayName",
+        "description",
+        "idle",
+        "idleStartMilliseconds",
+        "numExecutors",
+        "offline",
+        "offlineCauseReason",
+        "temporarilyOffline",
+    ]
+    .iter()
+    .fold(TreeBuilder::object(name), |tree, field| {
+        tree.with_subfield(*field)
+    })
+    .with_subfield(TreeBuilder::object("offlineCause").with_subfield("_class"))
+    .with_subfield(TreeBuilder::object("executors").with_subfield("idle"))
+    .with_subfield(TreeBuilder::object("assignedLabels").with_subfield("name"))
+    .with_subfield(
+        TreeBuilder::object("monitorData")
+            .with_subfield(TreeBuilder::object("*").with_subfield("*")),
+    )
+}
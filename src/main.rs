@@ -1,122 +1,975 @@
+use std::fs;
+use std::io;
 use std::iter;
-use std::{thread, time};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time;
 
 use chrono::Utc;
 use handlebars::Handlebars;
-use log::debug;
+use log::error;
 use serde::Serialize;
 
+mod batch;
 mod cli_config;
+mod completion;
+mod credentials;
+mod dashboard;
+mod exit_code;
 mod handlebars_helpers;
+mod output;
+mod polling;
 
-static HANDLEBARS_TEMPLATE: &str = "item_template";
+use exit_code::{ExitCodes, Outcome, OutcomeTracker};
+use output::Output;
+use polling::{sleep_unless, Backoff, Polling};
 
-fn main() -> Result<(), failure::Error> {
+fn main() {
     env_logger::init();
 
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        if let Some(hint) = error_hint(&err) {
+            eprintln!("hint: {}", hint);
+        }
+        std::process::exit(exit_code::ERROR);
+    }
+}
+
+fn error_hint(err: &failure::Error) -> Option<&'static str> {
+    match err.downcast_ref::<jencli::Error>()? {
+        jencli::Error::InvalidPattern { .. } => Some(
+            "patterns are regular expressions, special characters like `+` or `(` must be escaped with `\\`",
+        ),
+        jencli::Error::Unauthorized { .. } => {
+            Some("check the user and the password or API token, or store a token with `jencli login`")
+        }
+        jencli::Error::PermissionDenied { .. } => {
+            Some("the user is missing the Jenkins permission needed for this action")
+        }
+        jencli::Error::NotFound { .. } => Some(
+            "check the name of the job, with folders separated by `/`, `jencli search` can help finding it",
+        ),
+        jencli::Error::ChecksumMismatch { .. } => {
+            Some("the corrupted file was removed, downloading it again may fix it")
+        }
+        jencli::Error::InvalidConfig(_) => {
+            Some("the file must be a whole config.xml, like the one given by `jencli config get`")
+        }
+        jencli::Error::Server { .. } => Some("Jenkins failed to answer, its logs may have more details"),
+        jencli::Error::Network(_) => {
+            Some("check the Jenkins URL, and that Jenkins can be reached from this machine")
+        }
+        jencli::Error::InvalidParameter(_) | jencli::Error::Other(_) => None,
+    }
+}
+
+fn run() -> Result<(), failure::Error> {
     let opt = cli_config::load()?;
 
-    let jenkins = jencli::JenkinsInformation {
-        url: opt.url,
-        user: opt.user,
-        password: opt.password,
-        depth: opt.depth,
+    let mut output = new_output(opt.output, opt.columns.clone());
+    let exit_codes = ExitCodes::new(opt.exit_codes);
+    let outcome = OutcomeTracker::default();
+
+    let lines: Box<dyn Iterator<Item = String>> = match opt.command {
+        cli_config::CommandOpt::Profiles { template } => {
+            output.template(template)?;
+            Box::new(output.items(cli_config::JenkinsSettings::new()?.list_profiles()))
+        }
+        cli_config::CommandOpt::Completions { shell } => {
+            Box::new(iter::once(completion::script(shell)))
+        }
+        cli_config::CommandOpt::Login { template } => {
+            output.template(template)?;
+            let user = opt.user.ok_or_else(|| {
+                failure::err_msg("no Jenkins user configured, use --user, JENKINS_USER or a profile in .jencli.conf")
+            })?;
+            let token = rpassword::prompt_password_stderr(&format!("API token for {}: ", user))?;
+            let token = token.trim().to_string();
+            let jenkins = jencli::JenkinsInformation::new(
+                &jenkins_url(opt.url)?,
+                Some(user),
+                Some(token.clone()),
+                opt.depth.unwrap_or(1),
+            )?;
+            let current_user = jencli::get_current_user(&jenkins)?;
+            let path = credentials::stored_token_path(opt.profile.as_deref())
+                .ok_or_else(|| failure::err_msg("no configuration directory to store the token"))?;
+            credentials::store_token(&path, &token)?;
+            Box::new(output.items(iter::once(current_user)))
+        }
+        cli_config::CommandOpt::Complete { kind, prefix, ttl } => {
+            let url = jenkins_url(opt.url)?;
+            let (user, password, token_file) = (opt.user, opt.password, opt.token_file);
+            let (profile, profile_settings, depth) = (opt.profile, opt.profile_settings, opt.depth);
+            let connect = || {
+                let password = cli_config::load_password(
+                    password,
+                    token_file.as_deref(),
+                    profile.as_deref(),
+                    &profile_settings,
+                )?;
+                Ok(jencli::JenkinsInformation::new(
+                    &url,
+                    user,
+                    password,
+                    depth.unwrap_or(1),
+                )?)
+            };
+            Box::new(
+                completion::complete(&url, connect, &kind, prefix, time::Duration::from_secs(ttl))?
+                    .into_iter(),
+            )
+        }
+        command => {
+            let password = cli_config::load_password(
+                opt.password,
+                opt.token_file.as_deref(),
+                opt.profile.as_deref(),
+                &opt.profile_settings,
+            )?;
+            let jenkins = jencli::JenkinsInformation::new(
+                &jenkins_url(opt.url)?,
+                opt.user,
+                password,
+                opt.depth.unwrap_or(1),
+            )?;
+            match opt.watch {
+                Some(interval) => {
+                    if !command.is_listing() {
+                        return Err(failure::err_msg(
+                            "--watch only works with commands listing items, like running, job, view or build",
+                        ));
+                    }
+                    if opt.output.is_document() {
+                        return Err(failure::err_msg(
+                            "--watch redraws the items, it only works with the template or json output",
+                        ));
+                    }
+                    if !opt.until_results.is_empty() && !command.is_listing_builds() {
+                        return Err(failure::err_msg(
+                            "--until-result only works with the build and builds commands",
+                        ));
+                    }
+                    let mut results = vec![];
+                    for result in opt.until_results {
+                        if let serde_json::Value::String(result) = serde_json::to_value(result)? {
+                            results.push(result);
+                        }
+                    }
+                    let until = output::Until::new(opt.until_fields, results);
+                    let format = opt.output;
+                    let columns = opt.columns;
+                    watch(
+                        || {
+                            let mut output = new_output(format, columns.clone());
+                            output.until(until.clone());
+                            output
+                        },
+                        &until,
+                        jenkins,
+                        outcome.clone(),
+                        command,
+                        time::Duration::from_secs(interval),
+                    )?;
+                    Box::new(iter::empty())
+                }
+                None => command_to_iter(jenkins, output, outcome.clone(), command)?,
+            }
+        }
     };
 
+    lines.for_each(|string| println!("{}", string));
+    if let Some(outcome) = outcome.get() {
+        let code = exit_codes.code(outcome);
+        if code != 0 {
+            std::process::exit(code);
+        }
+    }
+    Ok(())
+}
+
+fn new_output(format: output::OutputFormat, columns: Option<Vec<String>>) -> Output {
     let mut render = Handlebars::new();
     render.register_escape_fn(handlebars::no_escape);
     render.register_helper("colored", Box::new(handlebars_helpers::colored_status));
     render.register_helper("date", Box::new(handlebars_helpers::date));
+    render.register_helper("len", Box::new(handlebars_helpers::len));
+    Output::new(render, format, columns)
+}
+
+/// Run a command every `interval` until `until` is met or Ctrl-C, redrawing its
+/// output and marking the lines that changed since the previous run
+fn watch<F>(
+    new_output: F,
+    until: &output::Until,
+    jenkins: jencli::JenkinsInformation,
+    outcome: OutcomeTracker,
+    command: cli_config::CommandOpt,
+    interval: time::Duration,
+) -> Result<(), failure::Error>
+where
+    F: Fn() -> Output,
+{
+    use crossterm::cursor::MoveTo;
+    use crossterm::execute;
+    use crossterm::terminal::{Clear, ClearType};
+
+    let header = format!(
+        "Every {}s: jencli {}",
+        interval.as_secs(),
+        std::env::args().skip(1).collect::<Vec<_>>().join(" ")
+    );
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::SIGINT, Arc::clone(&interrupted))?;
+    // a second Ctrl-C quits right away
+    signal_hook::cleanup::register(signal_hook::SIGINT, vec![signal_hook::SIGINT])?;
 
-    let output = command_to_iter(jenkins, render, opt.command)?;
+    let polling = Polling::new(Some(interval.as_secs()));
+    let mut backoff = Backoff::default();
+    let mut previous: Option<Vec<String>> = None;
+    loop {
+        let (lines, delay) = match command_to_iter(
+            jenkins.clone(),
+            new_output(),
+            outcome.clone(),
+            command.clone(),
+        ) {
+            Ok(lines) => {
+                backoff = Backoff::default();
+                (lines.collect::<Vec<_>>(), polling.next(true, None))
+            }
+            Err(err) => {
+                // server errors are retried with a growing delay, others stop watching
+                let delay = match err.downcast_ref().and_then(|err| backoff.retry(err)) {
+                    Some(delay) => delay,
+                    None => return Err(err),
+                };
+                let line = format!(
+                    "error: {}, retrying in {}",
+                    err,
+                    humantime::format_duration(delay)
+                );
+                (vec![line], delay)
+            }
+        };
+
+        execute!(std::io::stdout(), Clear(ClearType::All), MoveTo(0, 0))?;
+        println!("{}\t{}\n", header, Utc::now().format("%Y-%m-%d %H:%M:%S"));
+        for line in &lines {
+            let changed = previous
+                .as_ref()
+                .map(|previous| !previous.contains(line))
+                .unwrap_or(false);
+            if changed {
+                println!("{} {}", ansi_term::Style::new().reverse().paint(">"), line);
+            } else {
+                println!("  {}", line);
+            }
+        }
 
-    output.for_each(|string| println!("{}", string));
+        if until.is_met() || sleep_unless(&interrupted, delay) {
+            break;
+        }
+        previous = Some(lines);
+    }
+    signal_hook::cleanup::cleanup_signal(signal_hook::SIGINT)?;
     Ok(())
 }
 
+fn jenkins_url(url: Option<String>) -> Result<String, failure::Error> {
+    url.ok_or_else(|| {
+        failure::err_msg(
+            "no Jenkins URL configured, use --url, JENKINS_URL or a profile in .jencli.conf",
+        )
+    })
+}
+
 fn command_to_iter(
     jenkins: jencli::JenkinsInformation,
-    mut render: Handlebars,
+    mut output: Output,
+    outcome: OutcomeTracker,
     command: cli_config::CommandOpt,
 ) -> Result<Box<dyn Iterator<Item = String>>, failure::Error> {
     match command {
-        cli_config::CommandOpt::Search { pattern, template } => {
-            render.register_template_string(HANDLEBARS_TEMPLATE, template)?;
-            Ok(Box::new(item_to_template(
-                &mut render,
-                jencli::search_job(&jenkins, &pattern)?,
-            )))
+        cli_config::CommandOpt::Search {
+            pattern,
+            max_depth,
+            template,
+        } => {
+            output.template(template)?;
+            Ok(Box::new(
+                output.items(jencli::search_job(&jenkins, &pattern, max_depth)?),
+            ))
         }
         cli_config::CommandOpt::Job { name, template } => {
-            render.register_template_string(HANDLEBARS_TEMPLATE, template)?;
-            Ok(Box::new(item_to_template(
-                &mut render,
-                iter::once(jencli::get_job(&jenkins, &name)?),
-            )))
+            output.template(template)?;
+            Ok(Box::new(
+                output.items(iter::once(jencli::get_job(&jenkins, &name)?)),
+            ))
+        }
+        cli_config::CommandOpt::Config(cli_config::ConfigOpt::Get { name }) => Ok(Box::new(
+            jencli::get_job_config(&jenkins, &name)?
+                .lines()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )),
+        cli_config::CommandOpt::Config(cli_config::ConfigOpt::Diff {
+            name,
+            file,
+            template,
+        }) => {
+            output.template(template)?;
+            let current = jencli::get_job_config(&jenkins, &name)?;
+            let changes = jencli::job_config::diff(&current, &fs::read_to_string(&file)?)?;
+            Ok(Box::new(output.items(changes.into_iter())))
+        }
+        cli_config::CommandOpt::Config(cli_config::ConfigOpt::Push {
+            name,
+            file,
+            dry_run,
+            template,
+        }) => {
+            output.template(template)?;
+            Ok(Box::new(output.items(iter::once(jencli::push_job_config(
+                &jenkins,
+                &name,
+                &fs::read_to_string(&file)?,
+                dry_run,
+            )?))))
+        }
+        cli_config::CommandOpt::Enable {
+            name,
+            all_matching,
+            max_depth,
+            yes,
+            template,
+        } => {
+            output.template(template)?;
+            let confirm = !yes && all_matching.is_some();
+            let names = job_names(&jenkins, name, all_matching, max_depth, confirm, "enable")?;
+            let jobs = names
+                .iter()
+                .map(|name| jencli::set_job_enabled(&jenkins, name, true))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Box::new(output.items(jobs.into_iter())))
+        }
+        cli_config::CommandOpt::Disable {
+            name,
+            all_matching,
+            max_depth,
+            yes,
+            template,
+        } => {
+            output.template(template)?;
+            let confirm = !yes && all_matching.is_some();
+            let names = job_names(&jenkins, name, all_matching, max_depth, confirm, "disable")?;
+            let jobs = names
+                .iter()
+                .map(|name| jencli::set_job_enabled(&jenkins, name, false))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Box::new(output.items(jobs.into_iter())))
+        }
+        cli_config::CommandOpt::Copy {
+            name,
+            new_name,
+            template,
+        } => {
+            output.template(template)?;
+            Ok(Box::new(output.items(iter::once(jencli::copy_job(
+                &jenkins, &name, &new_name,
+            )?))))
+        }
+        cli_config::CommandOpt::Rename {
+            name,
+            new_name,
+            template,
+        } => {
+            output.template(template)?;
+            Ok(Box::new(output.items(iter::once(jencli::rename_job(
+                &jenkins, &name, &new_name,
+            )?))))
+        }
+        cli_config::CommandOpt::Delete {
+            name,
+            all_matching,
+            max_depth,
+            yes,
+            template,
+        } => {
+            output.template(template)?;
+            // deleting even a single job is confirmed
+            let names = job_names(&jenkins, name, all_matching, max_depth, !yes, "delete")?;
+            let jobs = names
+                .iter()
+                .map(|name| jencli::delete_job(&jenkins, name))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Box::new(output.items(jobs.into_iter())))
         }
         cli_config::CommandOpt::Build {
             name,
             number,
             template,
         } => {
-            render.register_template_string(HANDLEBARS_TEMPLATE, template)?;
-            Ok(Box::new(item_to_template(
-                &mut render,
-                iter::once(jencli::get_build(&jenkins, &name, number)?),
-            )))
+            output.template(template)?;
+            let build = jencli::get_build(&jenkins, &name, number)?;
+            outcome.record(Some(
+                build.result.map(Outcome::from).unwrap_or(Outcome::Running),
+            ));
+            Ok(Box::new(output.items(iter::once(build))))
+        }
+        cli_config::CommandOpt::Builds {
+            name,
+            last,
+            results,
+            since,
+            until,
+            user,
+            branch,
+            stats,
+            template,
+            stats_template,
+        } => {
+            let filter = jencli::history::BuildFilter {
+                results,
+                since,
+                until,
+                user,
+                branch,
+            };
+            let builds = jencli::list_builds(&jenkins, &name, last)?
+                .filter(|build| filter.matches(build))
+                .collect::<Vec<_>>();
+
+            if stats {
+                output.template(stats_template)?;
+                Ok(Box::new(output.items(iter::once(
+                    jencli::history::BuildStats::from_builds(&builds),
+                ))))
+            } else {
+                output.template(template)?;
+                Ok(Box::new(output.items(builds.into_iter())))
+            }
+        }
+        cli_config::CommandOpt::Tests {
+            name,
+            number,
+            stack_traces,
+            diff,
+            template,
+            diff_template,
+        } => {
+            let report = jencli::get_test_report(&jenkins, &name, number)?;
+
+            match diff {
+                Some(previous) => {
+                    output.template(diff_template)?;
+                    let previous = jencli::get_test_report(&jenkins, &name, Some(previous))?;
+                    Ok(Box::new(output.items(iter::once(
+                        jencli::test_report::TestDiff::new(&previous, &report),
+                    ))))
+                }
+                None => {
+                    output.template(template)?;
+                    Ok(Box::new(output.items(iter::once(
+                        jencli::test_report::TestSummary::new(&report, stack_traces),
+                    ))))
+                }
+            }
+        }
+        cli_config::CommandOpt::Artifacts {
+            name,
+            number,
+            latest_successful,
+            download,
+            dest,
+            template,
+            download_template,
+        } => {
+            let number = if latest_successful {
+                Some(jencli::get_last_successful_build_number(&jenkins, &name)?)
+            } else {
+                number
+            };
+            // sizes are only shown when listing, downloads get them from the files
+            let artifacts = jencli::list_artifacts(&jenkins, &name, number, download.is_none())?;
+
+            match download {
+                Some(pattern) => {
+                    output.template(download_template)?;
+                    let matching = artifacts
+                        .filter(|artifact| pattern.matches(&artifact.relative_path))
+                        .collect::<Vec<_>>();
+                    if matching.is_empty() {
+                        return Err(failure::format_err!(
+                            "no artifact of {} matches {}",
+                            name,
+                            pattern
+                        ));
+                    }
+                    let downloaded = matching
+                        .iter()
+                        .map(|artifact| jencli::download_artifact(&jenkins, artifact, &dest))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Box::new(output.items(downloaded.into_iter())))
+                }
+                None => {
+                    output.template(template)?;
+                    Ok(Box::new(output.items(artifacts)))
+                }
+            }
+        }
+        cli_config::CommandOpt::Log {
+            name,
+            number,
+            follow,
+            polling,
+        } => {
+            if follow {
+                Ok(Box::new(console_lines(jencli::follow_console(
+                    &jenkins,
+                    &name,
+                    number,
+                    time::Duration::from_secs(polling),
+                )?)))
+            } else {
+                Ok(Box::new(
+                    jencli::get_console(&jenkins, &name, number)?
+                        .lines()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                ))
+            }
+        }
+        cli_config::CommandOpt::Tui { view, refresh } => {
+            let dashboard = dashboard::Dashboard::new(&jenkins, view);
+            dashboard::run(dashboard, time::Duration::from_secs(refresh))?;
+            Ok(Box::new(iter::empty()))
+        }
+        cli_config::CommandOpt::Profiles { .. }
+        | cli_config::CommandOpt::Login { .. }
+        | cli_config::CommandOpt::Completions { .. }
+        | cli_config::CommandOpt::Complete { .. } => unreachable!(),
+        cli_config::CommandOpt::Stages {
+            name,
+            number,
+            log,
+            template,
+        } => match log {
+            Some(stage) => Ok(Box::new(
+                jencli::get_stage_log(&jenkins, &name, number, &stage)?
+                    .lines()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+            None => {
+                output.template(template)?;
+                Ok(Box::new(
+                    output.items(
+                        jencli::get_pipeline_run(&jenkins, &name, number)?
+                            .stages
+                            .into_iter(),
+                    ),
+                ))
+            }
+        },
+        cli_config::CommandOpt::Input {
+            name,
+            number,
+            id,
+            proceed,
+            abort,
+            params,
+            template,
+        } => {
+            output.template(template)?;
+            if proceed || abort {
+                Ok(Box::new(output.items(iter::once(jencli::answer_input(
+                    &jenkins,
+                    &name,
+                    number,
+                    id.as_deref(),
+                    proceed,
+                    &params.into_iter().collect(),
+                )?))))
+            } else {
+                Ok(Box::new(output.items(
+                    jencli::get_pending_inputs(&jenkins, &name, number)?.into_iter(),
+                )))
+            }
         }
         cli_config::CommandOpt::Views { pattern, template } => {
-            render.register_template_string(HANDLEBARS_TEMPLATE, template)?;
-            Ok(Box::new(item_to_template(
-                &mut render,
-                jencli::list_views(&jenkins, pattern)?,
-            )))
+            output.template(template)?;
+            Ok(Box::new(
+                output.items(jencli::list_views(&jenkins, pattern)?),
+            ))
         }
         cli_config::CommandOpt::View { name, template } => {
-            render.register_template_string(HANDLEBARS_TEMPLATE, template)?;
-            Ok(Box::new(item_to_template(
-                &mut render,
-                jencli::list_jobs_of_view(&jenkins, &name)?,
-            )))
+            output.template(template)?;
+            Ok(Box::new(
+                output.items(jencli::list_jobs_of_view(&jenkins, &name)?),
+            ))
         }
         cli_config::CommandOpt::Trigger {
             name,
+            params,
+            files,
+            params_file,
+            wait_start,
+            wait_finish,
+            log,
+            polling,
+            queue_timeout,
+            build_timeout,
+            abort_on_timeout,
+            template,
+        } => {
+            if log && output.format().is_document() {
+                return Err(failure::err_msg(
+                    "--log prints the console output between the statuses, it only works with the template or json output",
+                ));
+            }
+            output.template(template)?;
+            let timeouts = Timeouts {
+                queue: queue_timeout,
+                build: build_timeout,
+                abort: abort_on_timeout,
+            };
+
+            let mut parameters = jencli::parameters::BuildParameters::new();
+            if let Some(params_file) = params_file {
+                for (key, value) in cli_config::load_parameters(&params_file)? {
+                    parameters.insert(key, jencli::parameters::ParameterValue::Value(value));
+                }
+            }
+            for (key, value) in params {
+                parameters.insert(key, jencli::parameters::ParameterValue::Value(value));
+            }
+            for (key, path) in files {
+                parameters.insert(key, jencli::parameters::ParameterValue::File(path.into()));
+            }
+            if !parameters.is_empty() {
+                jencli::parameters::check_parameters(
+                    &jencli::get_job_parameters(&jenkins, &name)?,
+                    &parameters,
+                )?;
+            }
+
+            let item = jencli::trigger_job(&jenkins, &name, &parameters)?;
+
+            if log {
+                let (started, result) = command_trigger(
+                    &jenkins,
+                    &name,
+                    &item,
+                    true,
+                    false,
+                    Polling::new(polling),
+                    timeouts,
+                )?;
+                if started.last().map_or(true, |status| status.build.is_none()) {
+                    outcome.record(result);
+                    return Ok(Box::new(output.items(started.into_iter())));
+                }
+                let started = output.items(started.into_iter());
+                let number = jencli::get_queue_item(&jenkins, &item)?
+                    .executable
+                    .map(|build| build.number);
+
+                // the build timeout and Ctrl-C are checked while waiting for new output
+                let interrupted = Arc::new(AtomicBool::new(false));
+                signal_hook::flag::register(signal_hook::SIGINT, Arc::clone(&interrupted))?;
+                signal_hook::cleanup::register(signal_hook::SIGINT, vec![signal_hook::SIGINT])?;
+                let started_at = time::Instant::now();
+                let timed_out = move || {
+                    timeouts
+                        .build
+                        .map(|timeout| started_at.elapsed() >= timeout)
+                        .unwrap_or(false)
+                };
+                let stop = {
+                    let interrupted = Arc::clone(&interrupted);
+                    move || interrupted.load(Ordering::SeqCst) || timed_out()
+                };
+                let console = console_lines(jencli::follow_console_until(
+                    &jenkins,
+                    &name,
+                    number,
+                    time::Duration::from_secs(polling.unwrap_or(2)),
+                    stop,
+                )?);
+                let finished = iter::once(()).flat_map(move |_| {
+                    let finished = BuildAndQueue::from_short_queue_item(&jenkins, &item, &name)
+                        .map_err(|err| error!("{}", err))
+                        .ok();
+                    let result = finished.as_ref().and_then(BuildAndQueue::result);
+                    let stopped = match result {
+                        Some(_) => Ok(None),
+                        None => stop_following(
+                            &jenkins,
+                            &name,
+                            finished.as_ref(),
+                            interrupted.load(Ordering::SeqCst),
+                            timed_out(),
+                            timeouts,
+                        ),
+                    };
+                    match stopped {
+                        Ok(Some(stopped)) => outcome.record(Some(stopped)),
+                        Ok(None) => outcome.record_build(result),
+                        Err(err) => {
+                            error!("{}", err);
+                            outcome.record(Some(Outcome::Running));
+                        }
+                    }
+                    if let Err(err) = signal_hook::cleanup::cleanup_signal(signal_hook::SIGINT) {
+                        error!("{}", err);
+                    }
+                    output.items(finished.into_iter())
+                });
+                return Ok(Box::new(started.chain(console).chain(finished)));
+            }
+
+            let (statuses, result) = command_trigger(
+                &jenkins,
+                &name,
+                &item,
+                wait_start,
+                wait_finish,
+                Polling::new(polling),
+                timeouts,
+            )?;
+            outcome.record(result);
+            Ok(Box::new(output.items(statuses.into_iter())))
+        }
+        cli_config::CommandOpt::Rebuild {
+            name,
+            number,
+            params,
             wait_start,
             wait_finish,
             polling,
+            queue_timeout,
+            build_timeout,
+            abort_on_timeout,
             template,
         } => {
-            render.register_template_string(HANDLEBARS_TEMPLATE, template)?;
-            let item = jencli::trigger_job(&jenkins, &name)?;
+            output.template(template)?;
+
+            let overrides = params
+                .into_iter()
+                .map(|(key, value)| (key, jencli::parameters::ParameterValue::Value(value)))
+                .collect::<jencli::parameters::BuildParameters>();
+            if !overrides.is_empty() {
+                jencli::parameters::check_parameters(
+                    &jencli::get_job_parameters(&jenkins, &name)?,
+                    &overrides,
+                )?;
+            }
 
-            Ok(Box::new(item_to_template(
-                &mut render,
-                command_trigger(jenkins, name, item, wait_start, wait_finish, polling),
-            )))
+            let item = jencli::rebuild_job(&jenkins, &name, number, overrides)?;
+            let (statuses, result) = command_trigger(
+                &jenkins,
+                &name,
+                &item,
+                wait_start,
+                wait_finish,
+                Polling::new(polling),
+                Timeouts {
+                    queue: queue_timeout,
+                    build: build_timeout,
+                    abort: abort_on_timeout,
+                },
+            )?;
+            outcome.record(result);
+            Ok(Box::new(output.items(statuses.into_iter())))
         }
-        cli_config::CommandOpt::Running {
-            no_queued,
+        cli_config::CommandOpt::Replay {
+            name,
+            number,
+            script,
+            wait_start,
+            wait_finish,
+            polling,
+            queue_timeout,
+            build_timeout,
+            abort_on_timeout,
             template,
         } => {
-            render.register_template_string(HANDLEBARS_TEMPLATE, template)?;
+            output.template(template)?;
 
-            let jenkins2 = jenkins.clone();
+            let item = jencli::replay_build(&jenkins, &name, number, &fs::read_to_string(script)?)?;
+            let (statuses, result) = command_trigger(
+                &jenkins,
+                &name,
+                &item,
+                wait_start,
+                wait_finish,
+                Polling::new(polling),
+                Timeouts {
+                    queue: queue_timeout,
+                    build: build_timeout,
+                    abort: abort_on_timeout,
+                },
+            )?;
+            outcome.record(result);
+            Ok(Box::new(output.items(statuses.into_iter())))
+        }
+        cli_config::CommandOpt::Batch {
+            mut names,
+            all_matching,
+            jobs_file,
+            max_depth,
+            params,
+            max_parallel,
+            fail_fast,
+            polling,
+            template,
+        } => {
+            output.template(template)?;
 
-            let iter = item_to_template(
-                &mut render,
-                jencli::get_executors(&jenkins)?
-                    .map(move |build| BuildAndQueue::from_build(&jenkins, build)),
-            );
+            if let Some(pattern) = all_matching {
+                names
+                    .extend(jencli::search_job(&jenkins, &pattern, max_depth)?.map(|job| job.name));
+            }
+            if let Some(jobs_file) = jobs_file {
+                names.extend(
+                    fs::read_to_string(jobs_file)?
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(ToString::to_string),
+                );
+            }
+            if names.is_empty() {
+                return Err(failure::err_msg("no job to trigger"));
+            }
 
-            if !no_queued {
-                Ok(Box::new(iter.chain(item_to_template(
-                    &mut render,
-                    jencli::get_queue(&jenkins2)?.map(BuildAndQueue::from_queue_item),
-                ))))
+            let parameters = params
+                .into_iter()
+                .map(|(key, value)| (key, jencli::parameters::ParameterValue::Value(value)))
+                .collect::<jencli::parameters::BuildParameters>();
+            if !parameters.is_empty() {
+                for name in &names {
+                    jencli::parameters::check_parameters(
+                        &jencli::get_job_parameters(&jenkins, name)?,
+                        &parameters,
+                    )?;
+                }
+            }
+
+            let (builds, result) = batch::run(
+                &jenkins,
+                names,
+                &parameters,
+                max_parallel,
+                fail_fast,
+                Polling::new(polling),
+            )?;
+            outcome.record(result);
+            Ok(Box::new(output.items(builds.into_iter())))
+        }
+        cli_config::CommandOpt::Stop {
+            name,
+            number,
+            all_matching,
+            term,
+            kill,
+            grace,
+            template,
+        } => {
+            output.template(template)?;
+
+            let signal = if kill {
+                jencli::StopSignal::Kill
+            } else if term {
+                jencli::StopSignal::Term
             } else {
-                Ok(Box::new(iter))
+                jencli::StopSignal::Stop
+            };
+            let grace = time::Duration::from_secs(grace);
+            match (name, all_matching) {
+                (_, Some(pattern)) => {
+                    let (stopped, failed): (Vec<_>, Vec<_>) =
+                        jencli::stop_matching_builds(&jenkins, &pattern, signal, grace)?
+                            .partition(Result::is_ok);
+                    // builds stopped are listed before the ones that could not be
+                    let failed = iter::once(()).flat_map(move |_| {
+                        for err in failed.iter().filter_map(|result| result.as_ref().err()) {
+                            eprintln!("error: {}", err);
+                        }
+                        if !failed.is_empty() {
+                            eprintln!(
+                                "error: could not stop {} of the matching builds",
+                                failed.len()
+                            );
+                            outcome.record(Some(Outcome::Error));
+                        }
+                        iter::empty()
+                    });
+                    Ok(Box::new(
+                        output
+                            .items(stopped.into_iter().filter_map(Result::ok))
+                            .chain(failed),
+                    ))
+                }
+                (Some(name), None) => Ok(Box::new(output.items(iter::once(jencli::stop_build(
+                    &jenkins, &name, number, signal, grace,
+                )?)))),
+                (None, None) => unreachable!(),
             }
         }
+        cli_config::CommandOpt::Cancel { id, template } => {
+            output.template(template)?;
+
+            let item = jencli::get_queue_item_from_id(&jenkins, id)?;
+            jencli::cancel_queue_item(&jenkins, id)?;
+            Ok(Box::new(output.items(iter::once(item))))
+        }
+        cli_config::CommandOpt::Nodes { pattern, template } => {
+            output.template(template)?;
+            Ok(Box::new(
+                output.items(jencli::list_nodes(&jenkins, pattern)?),
+            ))
+        }
+        cli_config::CommandOpt::Node(cli_config::NodeOpt::Offline {
+            name,
+            reason,
+            template,
+        }) => {
+            output.template(template)?;
+            Ok(Box::new(output.items(iter::once(
+                jencli::set_node_offline(&jenkins, &name, true, reason.as_deref())?,
+            ))))
+        }
+        cli_config::CommandOpt::Node(cli_config::NodeOpt::Online { name, template }) => {
+            output.template(template)?;
+            Ok(Box::new(output.items(iter::once(
+                jencli::set_node_offline(&jenkins, &name, false, None)?,
+            ))))
+        }
+        cli_config::CommandOpt::Running {
+            no_queued,
+            template,
+        } => {
+            output.template(template)?;
+
+            let building = jencli::get_executors(&jenkins)?
+                .map(|build| BuildAndQueue::from_build(&jenkins, build))
+                .collect::<Vec<_>>();
+            let queued = if !no_queued {
+                jencli::get_queue(&jenkins)?
+                    .map(BuildAndQueue::from_queue_item)
+                    .collect::<Vec<_>>()
+            } else {
+                vec![]
+            };
+
+            Ok(Box::new(output.items(building.into_iter().chain(queued))))
+        }
     }
 }
 
@@ -132,29 +985,82 @@ struct EnrichedBuild {
     #[serde(flatten)]
     build: Option<jenkins_api::build::CommonBuild>,
     elapsed: Option<i64>,
+    /// expected end of a running build, in seconds since epoch, from its estimated duration
+    eta: Option<i64>,
+    /// seconds left before the expected end of a running build, 0 once late
+    remaining: Option<i64>,
     progress: Option<u32>,
     node: Option<String>,
+    /// stage running, or last stage once finished, of a Pipeline build
+    stage: Option<jencli::stages::Stage>,
 }
+impl EnrichedBuild {
+    fn new(
+        build: Option<jenkins_api::build::CommonBuild>,
+        node: Option<String>,
+        progress: Option<u32>,
+        stage: Option<jencli::stages::Stage>,
+    ) -> Self {
+        let now = Utc::now().timestamp();
+        let eta = build
+            .as_ref()
+            .filter(|build| build.building && build.estimated_duration > 0)
+            .map(|build| (build.timestamp as i64 + build.estimated_duration) / 1000);
+        EnrichedBuild {
+            elapsed: build
+                .as_ref()
+                .map(|build| now - build.timestamp as i64 / 1000),
+            eta,
+            remaining: eta.map(|eta| (eta - now).max(0)),
+            build,
+            node,
+            progress,
+            stage,
+        }
+    }
+}
+
 impl BuildAndQueue {
     fn from_short_queue_item(
         jenkins: &jencli::JenkinsInformation,
         item: &jenkins_api::queue::ShortQueueItem,
         name: &str,
-    ) -> Self {
-        let queue = jencli::get_queue_item(&jenkins, &item).unwrap();
-        let build = queue.executable.as_ref().map(|build| {
-            let full_build = jencli::get_build(&jenkins, &name, Some(build.number)).unwrap();
-            EnrichedBuild {
-                elapsed: Some(Utc::now().timestamp() - full_build.timestamp as i64 / 1000),
-                build: Some(full_build),
-                node: None,
-                progress: None,
+    ) -> Result<Self, jencli::Error> {
+        let queue = jencli::get_queue_item(jenkins, item)?;
+        let build = match queue.executable {
+            Some(ref build) => {
+                let full_build = jencli::get_build(jenkins, name, Some(build.number))?;
+                let stage =
+                    if full_build.class.as_deref() == Some(jencli::stages::PIPELINE_RUN_CLASS) {
+                        jencli::get_pipeline_run(jenkins, name, Some(build.number))
+                            .ok()
+                            .and_then(|run| run.current_stage().cloned())
+                    } else {
+                        None
+                    };
+                Some(EnrichedBuild::new(Some(full_build), None, None, stage))
             }
-        });
-        BuildAndQueue {
+            None => None,
+        };
+        Ok(BuildAndQueue {
             build,
             queue_item: Some(queue),
-        }
+        })
+    }
+
+    /// The queue item was cancelled before the build started
+    fn is_cancelled(&self) -> bool {
+        self.queue_item
+            .as_ref()
+            .map(|queue_item| queue_item.cancelled == Some(true) && queue_item.executable.is_none())
+            .unwrap_or(false)
+    }
+
+    fn result(&self) -> Option<jenkins_api::build::BuildStatus> {
+        self.build
+            .as_ref()
+            .and_then(|build| build.build.as_ref())
+            .and_then(|build| build.result)
     }
 
     fn from_queue_item(item: jenkins_api::queue::QueueItem) -> Self {
@@ -170,87 +1076,257 @@ impl BuildAndQueue {
         //     .clone()
         //     .and_then(|build| jencli::get_queue_item_from_id(&jenkins, build.queue_id).ok());
         // println!("{:#?}", queue);
-        let enriched_build = EnrichedBuild {
-            elapsed: build
-                .build
-                .clone()
-                .map(|build| Utc::now().timestamp() - build.timestamp as i64 / 1000),
-            build: build.build,
-            node: Some(build.node),
-            progress: Some(build.progress),
-        };
+        let enriched_build =
+            EnrichedBuild::new(build.build, Some(build.node), Some(build.progress), None);
         BuildAndQueue {
             build: Some(enriched_build),
             queue_item: None,
         }
     }
 }
+/// Jobs named on the command line or matching a regex, after confirmation if asked
+fn job_names(
+    jenkins: &jencli::JenkinsInformation,
+    name: Option<String>,
+    all_matching: Option<String>,
+    max_depth: u8,
+    confirm: bool,
+    action: &str,
+) -> Result<Vec<String>, failure::Error> {
+    let names = match (name, all_matching) {
+        (_, Some(pattern)) => jencli::search_job(jenkins, &pattern, max_depth)?
+            .map(|job| job.name)
+            .collect(),
+        (Some(name), None) => vec![name],
+        (None, None) => unreachable!(),
+    };
+    if !confirm || names.is_empty() {
+        return Ok(names);
+    }
+
+    eprintln!("About to {} {} job(s):", action, names.len());
+    for name in &names {
+        eprintln!("  {}", name);
+    }
+    if ask_yes_no("Continue?")? {
+        Ok(names)
+    } else {
+        Err(failure::format_err!(
+            "did not {} any job, use --yes to skip the confirmation",
+            action
+        ))
+    }
+}
+
+/// Ask a yes or no question on stderr, no being the default
+fn ask_yes_no(question: &str) -> Result<bool, io::Error> {
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// How long to wait for a triggered build, and what to do once it is too long
+#[derive(Debug, Clone, Copy, Default)]
+struct Timeouts {
+    /// time the build can spend in the queue
+    queue: Option<time::Duration>,
+    /// time the build can run
+    build: Option<time::Duration>,
+    /// cancel the queue item or stop the build on timeout
+    abort: bool,
+}
+
+/// Statuses of a triggered build until it starts or finishes, as asked, and
+/// the outcome to exit with: the build result, `Timeout`, or `Aborted` if the
+/// queue item was cancelled. On Ctrl-C, offers to abort the build.
 fn command_trigger(
-    jenkins: jencli::JenkinsInformation,
-    name: String,
-    item: jenkins_api::queue::ShortQueueItem,
+    jenkins: &jencli::JenkinsInformation,
+    name: &str,
+    item: &jenkins_api::queue::ShortQueueItem,
     wait_start: bool,
     wait_finish: bool,
-    polling: u64,
-) -> impl Iterator<Item = impl Serialize> {
-    let moved_jenkins = jenkins.clone();
-    let moved_name = name.clone();
-    let moved_item = item.clone();
-
-    iter::once(BuildAndQueue::from_short_queue_item(&jenkins, &item, &name))
-        .chain(iter::once(BuildAndQueue::from_short_queue_item(
-            &jenkins, &item, &name,
-        )))
-        .chain(iter::repeat(moved_item).map(move |item| {
-            thread::sleep(time::Duration::from_millis(polling * 1000));
-            BuildAndQueue::from_short_queue_item(&moved_jenkins, &item, &moved_name)
-        }))
-        .enumerate()
-        .take_while(move |(i, item)| {
-            *i == 0
-                || (wait_start
-                    && !if let Some(ref qi) = item.queue_item {
-                        qi.executable.is_some()
-                    } else {
-                        false
-                    })
-                || (wait_finish
-                    && !item
-                        .build
-                        .as_ref()
-                        .map(|build| {
-                            if let Some(ref b) = build.build {
-                                b.result.is_some()
-                            } else {
-                                false
-                            }
-                        })
-                        .unwrap_or(false))
-        })
-        .filter(|(i, _)| *i != 1)
-        .map(|(_, item)| item)
-        .chain(
-            iter::once(())
-                .map(move |_| BuildAndQueue::from_short_queue_item(&jenkins, &item, &name))
-                .take_while(move |_| wait_finish || wait_start),
-        )
+    polling: Polling,
+    timeouts: Timeouts,
+) -> Result<(Vec<BuildAndQueue>, Option<Outcome>), failure::Error> {
+    let waiting = |status: &BuildAndQueue| {
+        let started = status
+            .queue_item
+            .as_ref()
+            .map(|queue_item| queue_item.executable.is_some())
+            .unwrap_or(false);
+        (wait_start && !started) || (wait_finish && status.result().is_none())
+    };
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    if wait_start || wait_finish {
+        signal_hook::flag::register(signal_hook::SIGINT, Arc::clone(&interrupted))?;
+        // a second Ctrl-C quits right away
+        signal_hook::cleanup::register(signal_hook::SIGINT, vec![signal_hook::SIGINT])?;
+    }
+
+    let queued_at = time::Instant::now();
+    let mut started_at = None;
+    let mut status = poll_status(jenkins, item, name, &interrupted)?;
+    let mut statuses = vec![];
+    let result = loop {
+        if status.is_cancelled() {
+            eprintln!("{} was cancelled before starting", name);
+            break Some(Outcome::Aborted);
+        }
+        if !waiting(&status) {
+            break status.result().map(Outcome::from);
+        }
+
+        let (timeout, since, waiting_for) = match status.build {
+            None => (timeouts.queue, queued_at, "start"),
+            Some(_) => (
+                timeouts.build,
+                *started_at.get_or_insert_with(time::Instant::now),
+                "finish",
+            ),
+        };
+        if let Some(timeout) = timeout.filter(|timeout| since.elapsed() >= *timeout) {
+            eprintln!(
+                "timed out after {} waiting for {} to {}",
+                humantime::format_duration(timeout),
+                name,
+                waiting_for
+            );
+            if timeouts.abort {
+                abort_triggered(jenkins, name, &status)?;
+            }
+            break Some(Outcome::Timeout);
+        }
+
+        let delay = polling.next(
+            status.build.is_some(),
+            status.build.as_ref().and_then(|build| build.remaining),
+        );
+        let stopped = sleep_unless(&interrupted, delay);
+        let polled = poll_status(jenkins, item, name, &interrupted);
+        // Ctrl-C can also come while retrying after server errors, then the
+        // last known status is used to abort the build
+        if stopped || interrupted.load(Ordering::SeqCst) {
+            if let Ok(polled) = polled {
+                statuses.push(status);
+                status = polled;
+            }
+            if !ask_yes_no(&format!("\nAbort {} on Jenkins?", name))? {
+                return Err(failure::format_err!(
+                    "stopped waiting, {} is still queued or running on Jenkins",
+                    name
+                ));
+            }
+            abort_triggered(jenkins, name, &status)?;
+            status = BuildAndQueue::from_short_queue_item(jenkins, item, name).unwrap_or(status);
+            break Some(
+                status
+                    .result()
+                    .map(Outcome::from)
+                    .unwrap_or(Outcome::Aborted),
+            );
+        }
+        statuses.push(status);
+        status = polled?;
+    };
+    statuses.push(status);
+    if wait_start || wait_finish {
+        signal_hook::cleanup::cleanup_signal(signal_hook::SIGINT)?;
+    }
+    Ok((statuses, result))
+}
+
+/// Outcome of a build whose console output stopped being followed before it
+/// finished: `Timeout` once over the build timeout, or, on Ctrl-C, `Aborted` if
+/// the user chose to abort it and `Running` otherwise
+fn stop_following(
+    jenkins: &jencli::JenkinsInformation,
+    name: &str,
+    status: Option<&BuildAndQueue>,
+    interrupted: bool,
+    timed_out: bool,
+    timeouts: Timeouts,
+) -> Result<Option<Outcome>, failure::Error> {
+    if interrupted {
+        if !ask_yes_no(&format!("\nAbort {} on Jenkins?", name))? {
+            eprintln!("stopped following, {} is still running on Jenkins", name);
+            return Ok(Some(Outcome::Running));
+        }
+        if let Some(status) = status {
+            abort_triggered(jenkins, name, status)?;
+        }
+        return Ok(Some(Outcome::Aborted));
+    }
+    match timeouts.build.filter(|_| timed_out) {
+        Some(timeout) => {
+            eprintln!(
+                "timed out after {} waiting for {} to finish",
+                humantime::format_duration(timeout),
+                name
+            );
+            if timeouts.abort {
+                if let Some(status) = status {
+                    abort_triggered(jenkins, name, status)?;
+                }
+            }
+            Ok(Some(Outcome::Timeout))
+        }
+        None => Ok(None),
+    }
 }
 
-fn item_to_template<T, IT>(render: &mut Handlebars, items: T) -> impl Iterator<Item = String>
+/// Status of a triggered build, retrying with a growing delay while Jenkins
+/// answers with server errors, until Ctrl-C
+fn poll_status(
+    jenkins: &jencli::JenkinsInformation,
+    item: &jenkins_api::queue::ShortQueueItem,
+    name: &str,
+    interrupted: &AtomicBool,
+) -> Result<BuildAndQueue, jencli::Error> {
+    let mut backoff = Backoff::default();
+    loop {
+        let err = match BuildAndQueue::from_short_queue_item(jenkins, item, name) {
+            Ok(status) => return Ok(status),
+            Err(err) => err,
+        };
+        match backoff.retry(&err) {
+            Some(delay) => {
+                eprintln!("{}, retrying in {}", err, humantime::format_duration(delay));
+                if sleep_unless(interrupted, delay) {
+                    return Err(err);
+                }
+            }
+            None => return Err(err),
+        }
+    }
+}
+
+/// Cancel the queue item of a triggered build, or stop the build once started
+fn abort_triggered(
+    jenkins: &jencli::JenkinsInformation,
+    name: &str,
+    status: &BuildAndQueue,
+) -> Result<(), jencli::Error> {
+    match status.queue_item {
+        Some(ref queue_item) => match queue_item.executable {
+            Some(ref build) => jencli::stop_build(
+                jenkins,
+                name,
+                Some(build.number),
+                jencli::StopSignal::Stop,
+                time::Duration::from_secs(0),
+            )
+            .map(|_| ()),
+            None => jencli::cancel_queue_item(jenkins, queue_item.id as i32),
+        },
+        None => Ok(()),
+    }
+}
+
+fn console_lines<T>(lines: T) -> impl Iterator<Item = String>
 where
-    T: Iterator<Item = IT>,
-    IT: Serialize,
+    T: Iterator<Item = Result<String, jencli::Error>>,
 {
-    items
-        .map(move |item| {
-            debug!("{}", serde_json::to_string(&item).unwrap());
-            render
-                .render(HANDLEBARS_TEMPLATE, &item)
-                .map(|s| s.replace("\\t", "\t"))
-                .map(|s| s.replace("\\n", "\n"))
-                .ok()
-        })
-        .filter_map(|result| result)
-        .collect::<Vec<_>>()
-        .into_iter()
+    lines.filter_map(|line| line.map_err(|err| error!("{}", err)).ok())
 }
@@ -0,0 +1,1490 @@
+//! In-process HTTP server emulating the parts of the Jenkins JSON API used by
+//! jencli, to test it without a Jenkins server.
+//!
+//! Triggered builds go through the queue, run on an executor, then finish.
+//! Each step happens when the queue item or the build is polled, as set by
+//! the `Lifecycle` of the job, or when `MockJenkins::tick` is called.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use jenkins_api::build::BuildStatus;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// How a triggered build moves from the queue to its result
+#[derive(Debug, Clone, Copy)]
+pub struct Lifecycle {
+    /// number of polls of the queue item showing it waiting, before the build starts
+    pub queued_polls: u32,
+    /// number of polls of the build showing it running, before it finishes
+    pub running_polls: u32,
+    /// result of the build once finished
+    pub result: BuildStatus,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle {
+            queued_polls: 1,
+            running_polls: 1,
+            result: BuildStatus::Success,
+        }
+    }
+}
+
+struct Build {
+    number: u32,
+    result: Option<BuildStatus>,
+    polls: u32,
+    timestamp: u64,
+    node: Option<String>,
+    queue_id: u32,
+    console: String,
+    artifacts: Vec<(String, Vec<u8>)>,
+    test_report: Option<Value>,
+    /// `input` steps waiting for an answer, pausing the build
+    inputs: Vec<Value>,
+    /// answered inputs, with the parameters submitted
+    answers: Vec<(String, Value)>,
+    parameters: Vec<(String, String)>,
+    /// Pipeline script given when replaying a build
+    script: Option<String>,
+}
+
+#[derive(Default)]
+struct Job {
+    lifecycle: Lifecycle,
+    builds: Vec<Build>,
+    parameters: Vec<Value>,
+    /// config.xml, generated from the job if not pushed
+    config: Option<String>,
+    disabled: bool,
+    /// stages of a Pipeline job, empty for a freestyle job
+    stages: Vec<String>,
+}
+
+impl Job {
+    fn config_xml(&self) -> String {
+        self.config.clone().unwrap_or_else(|| {
+            "<?xml version='1.1' encoding='UTF-8'?>\n\
+             <project>\n  \
+               <description></description>\n  \
+               <keepDependencies>false</keepDependencies>\n  \
+               <builders>\n    \
+                 <hudson.tasks.Shell>\n      \
+                   <command>make</command>\n    \
+                 </hudson.tasks.Shell>\n  \
+               </builders>\n\
+             </project>\n"
+                .to_string()
+        })
+    }
+}
+
+struct Node {
+    name: String,
+    executors: usize,
+    labels: Vec<String>,
+    offline_reason: Option<String>,
+}
+
+struct QueueItem {
+    id: u32,
+    job: String,
+    polls: u32,
+    in_queue_since: u64,
+    build: Option<u32>,
+    cancelled: bool,
+    parameters: Vec<(String, String)>,
+    script: Option<String>,
+}
+
+struct State {
+    url: String,
+    /// path Jenkins is served under, like `/jenkins`, empty at the root
+    context_path: String,
+    jobs: BTreeMap<String, Job>,
+    views: BTreeMap<String, Vec<String>>,
+    nodes: Vec<Node>,
+    queue: Vec<QueueItem>,
+    next_queue_id: u32,
+    clock: u64,
+    requests: Vec<String>,
+    /// `Authorization` header of the last request
+    authorization: Option<String>,
+    /// number of the next polls of queue items to answer with 503 Service Unavailable
+    failing_polls: u32,
+    /// paths answered with 500 Internal Server Error
+    failing_paths: Vec<String>,
+}
+
+/// A mock Jenkins server, listening on a local port until dropped
+pub struct MockJenkins {
+    url: String,
+    state: Arc<Mutex<State>>,
+    server: Arc<Server>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MockJenkins {
+    /// Start a server with a `master` node of two executors, and no jobs
+    pub fn start() -> Self {
+        MockJenkins::start_under("")
+    }
+
+    /// Start a server under a context path, like `/jenkins`, as when Jenkins
+    /// is behind a reverse proxy
+    pub fn start_under(context_path: &str) -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").expect("could not start mock Jenkins"));
+        let url = format!("http://{}{}", server.server_addr(), context_path);
+        let state = Arc::new(Mutex::new(State {
+            url: url.clone(),
+            context_path: context_path.to_string(),
+            jobs: BTreeMap::new(),
+            views: BTreeMap::new(),
+            nodes: vec![Node {
+                name: "master".to_string(),
+                executors: 2,
+                labels: vec![],
+                offline_reason: None,
+            }],
+            queue: vec![],
+            next_queue_id: 1,
+            clock: 1_600_000_000_000,
+            failing_polls: 0,
+            failing_paths: vec![],
+            requests: vec![],
+            authorization: None,
+        }));
+
+        let thread = {
+            let server = server.clone();
+            let state = state.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(&state, request);
+                }
+            })
+        };
+
+        MockJenkins {
+            url,
+            state,
+            server,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Connection to this server, without credentials
+    pub fn jenkins(&self) -> crate::JenkinsInformation {
+        crate::JenkinsInformation::new(&self.url, None, None, 1)
+            .expect("could not connect to mock Jenkins")
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("mock Jenkins state poisoned")
+    }
+
+    /// Add a job, with folders separated by `/` in its name
+    pub fn add_job(&self, name: &str, lifecycle: Lifecycle) {
+        self.state().jobs.insert(
+            name.to_string(),
+            Job {
+                lifecycle,
+                ..Job::default()
+            },
+        );
+    }
+
+    /// Replace the config.xml of a job
+    pub fn set_config(&self, job: &str, config: &str) {
+        self.state().job_mut(job).config = Some(config.to_string());
+    }
+
+    /// Current config.xml of a job
+    pub fn config(&self, job: &str) -> Option<String> {
+        self.state().jobs.get(job).map(|job| job.config_xml())
+    }
+
+    /// Make a job a Pipeline job running these stages one after the other
+    pub fn set_stages(&self, job: &str, stages: &[&str]) {
+        self.state().job_mut(job).stages = stages.iter().map(ToString::to_string).collect();
+    }
+
+    /// Declare a string parameter of a job
+    pub fn add_parameter(&self, job: &str, name: &str, default: &str) {
+        self.state().job_mut(job).parameters.push(json!({
+            "_class": "hudson.model.StringParameterDefinition",
+            "name": name,
+            "type": "StringParameterDefinition",
+            "description": null,
+            "defaultParameterValue": { "value": default },
+        }));
+    }
+
+    /// Add a file parameter to a job, uploaded when triggering a build
+    pub fn add_file_parameter(&self, job: &str, name: &str) {
+        self.state().job_mut(job).parameters.push(json!({
+            "_class": "hudson.model.FileParameterDefinition",
+            "name": name,
+            "type": "FileParameterDefinition",
+            "description": null,
+            "defaultParameterValue": null,
+        }));
+    }
+
+    /// Add a build to the history of a job, running if it has no result, and
+    /// return its number
+    pub fn add_build(&self, job: &str, result: Option<BuildStatus>) -> u32 {
+        let mut state = self.state();
+        let node = match result {
+            Some(_) => None,
+            None => state.free_node(),
+        };
+        let timestamp = state.tick_clock();
+        let job = state.job_mut(job);
+        let number = job.builds.len() as u32 + 1;
+        job.builds.push(Build {
+            number,
+            result,
+            polls: 0,
+            timestamp,
+            node,
+            queue_id: 0,
+            console: match result {
+                Some(result) => format!("Started\nFinished: {}\n", status_name(result)),
+                None => "Started\n".to_string(),
+            },
+            artifacts: vec![],
+            test_report: None,
+            inputs: vec![],
+            answers: vec![],
+            parameters: vec![],
+            script: None,
+        });
+        number
+    }
+
+    pub fn add_artifact(&self, job: &str, number: u32, path: &str, content: &[u8]) {
+        self.state()
+            .build_mut(job, number)
+            .artifacts
+            .push((path.to_string(), content.to_vec()));
+    }
+
+    /// Set the test report of a build, as returned by `testReport/api/json`
+    pub fn set_test_report(&self, job: &str, number: u32, report: Value) {
+        self.state().build_mut(job, number).test_report = Some(report);
+    }
+
+    /// Pause a running build on an `input` step asking for these string
+    /// parameters, as (name, default value)
+    pub fn add_input(
+        &self,
+        job: &str,
+        number: u32,
+        id: &str,
+        message: &str,
+        parameters: &[(&str, &str)],
+    ) {
+        self.state().build_mut(job, number).inputs.push(json!({
+            "id": id,
+            "proceedText": "Proceed",
+            "message": message,
+            "inputs": parameters.iter().map(|(name, default)| json!({
+                "type": "StringParameterDefinition",
+                "name": name,
+                "description": "",
+                "definition": {
+                    "defaultParameterValue": { "name": name, "value": default },
+                    "description": "",
+                    "name": name,
+                    "type": "StringParameterDefinition",
+                },
+            })).collect::<Vec<_>>(),
+        }));
+    }
+
+    /// Inputs of a build that were proceeded with, as (id, submitted parameters)
+    pub fn input_answers(&self, job: &str, number: u32) -> Vec<(String, Value)> {
+        self.state().build_mut(job, number).answers.clone()
+    }
+
+    /// Parameters a build was triggered with, sorted by name
+    pub fn build_parameters(&self, job: &str, number: u32) -> Vec<(String, String)> {
+        self.state().build_mut(job, number).parameters.clone()
+    }
+
+    /// Pipeline script a build was replayed with
+    pub fn script(&self, job: &str, number: u32) -> Option<String> {
+        self.state().build_mut(job, number).script.clone()
+    }
+
+    /// Add a list view showing these jobs. The `all` view always shows all jobs.
+    pub fn add_view(&self, name: &str, jobs: &[&str]) {
+        self.state().views.insert(
+            name.to_string(),
+            jobs.iter().map(ToString::to_string).collect(),
+        );
+    }
+
+    pub fn add_node(&self, name: &str, executors: usize) {
+        self.state().nodes.push(Node {
+            name: name.to_string(),
+            executors,
+            labels: vec![],
+            offline_reason: None,
+        });
+    }
+
+    pub fn add_label(&self, node: &str, label: &str) {
+        if let Some(node) = self.state().node_mut(node) {
+            node.labels.push(label.to_string());
+        }
+    }
+
+    /// Mark a node offline, no build is started on it until it is back online
+    pub fn set_offline(&self, node: &str, reason: &str) {
+        if let Some(node) = self.state().node_mut(node) {
+            node.offline_reason = Some(reason.to_string());
+        }
+    }
+
+    /// Put a job in the queue, as if triggered, and return the id of the queue item
+    pub fn enqueue(&self, job: &str) -> u32 {
+        self.state().enqueue(job)
+    }
+
+    /// Cancel a queued item, as done from the Jenkins UI
+    pub fn cancel(&self, id: u32) {
+        if let Some(item) = self.state().queue.iter_mut().find(|item| item.id == id) {
+            item.cancelled = true;
+        }
+    }
+
+    /// Answer the next `count` polls of queue items with 503 Service
+    /// Unavailable, as Jenkins does while restarting
+    pub fn fail_queue_polls(&self, count: u32) {
+        self.state().failing_polls = count;
+    }
+
+    /// Answer all requests to `path`, like `/job/app/1/stop`, with 500 Internal
+    /// Server Error
+    pub fn fail_requests_to(&self, path: &str) {
+        self.state().failing_paths.push(path.to_string());
+    }
+
+    /// Advance all queued items and running builds by one poll
+    pub fn tick(&self) {
+        let mut state = self.state();
+        // builds started by this tick are only polled by the next one
+        let running = state
+            .jobs
+            .iter()
+            .flat_map(|(name, job)| {
+                job.builds
+                    .iter()
+                    .filter(|build| build.result.is_none())
+                    .map(move |build| (name.clone(), build.number))
+            })
+            .collect::<Vec<_>>();
+        let ids = state.queue.iter().map(|item| item.id).collect::<Vec<_>>();
+        for id in ids {
+            state.poll_queue_item(id);
+        }
+        for (job, number) in running {
+            state.poll_build(&job, number);
+        }
+    }
+
+    /// Requests received so far, as `METHOD /path?query`
+    pub fn requests(&self) -> Vec<String> {
+        self.state().requests.clone()
+    }
+
+    /// `Authorization` header of the last request, if it had one
+    pub fn authorization(&self) -> Option<String> {
+        self.state().authorization.clone()
+    }
+}
+
+impl Drop for MockJenkins {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn status_name(status: BuildStatus) -> String {
+    serde_json::to_value(status)
+        .ok()
+        .and_then(|value| value.as_str().map(ToString::to_string))
+        .unwrap_or_default()
+}
+
+fn color(status: Option<BuildStatus>) -> &'static str {
+    match status {
+        Some(BuildStatus::Success) => "blue",
+        Some(BuildStatus::Unstable) => "yellow",
+        Some(BuildStatus::Failure) => "red",
+        Some(BuildStatus::Aborted) => "aborted",
+        Some(BuildStatus::NotBuilt) | None => "notbuilt",
+    }
+}
+
+fn job_path(name: &str) -> String {
+    name.split('/')
+        .map(|segment| format!("job/{}/", urlencoding::encode(segment)))
+        .collect()
+}
+
+impl State {
+    fn job_mut(&mut self, name: &str) -> &mut Job {
+        self.jobs
+            .get_mut(name)
+            .unwrap_or_else(|| panic!("no job {} in mock Jenkins", name))
+    }
+
+    fn build(&self, job: &str, number: u32) -> &Build {
+        self.jobs[job]
+            .builds
+            .iter()
+            .find(|build| build.number == number)
+            .unwrap_or_else(|| panic!("no build {} #{} in mock Jenkins", job, number))
+    }
+
+    fn build_mut(&mut self, job: &str, number: u32) -> &mut Build {
+        self.job_mut(job)
+            .builds
+            .iter_mut()
+            .find(|build| build.number == number)
+            .unwrap_or_else(|| panic!("no build {} #{} in mock Jenkins", job, number))
+    }
+
+    fn tick_clock(&mut self) -> u64 {
+        self.clock += 1000;
+        self.clock
+    }
+
+    fn job_url(&self, name: &str) -> String {
+        format!("{}/{}", self.url, job_path(name))
+    }
+
+    /// Node from its name in urls, where the master is `(master)`
+    fn node_mut(&mut self, name: &str) -> Option<&mut Node> {
+        let name = if name == "(master)" { "master" } else { name };
+        self.nodes.iter_mut().find(|node| node.name == name)
+    }
+
+    fn busy_executors(&self, node: &str) -> usize {
+        self.jobs
+            .values()
+            .flat_map(|job| job.builds.iter())
+            .filter(|build| build.result.is_none() && build.node.as_deref() == Some(node))
+            .count()
+    }
+
+    fn free_node(&self) -> Option<String> {
+        self.nodes
+            .iter()
+            .find(|node| {
+                node.offline_reason.is_none() && self.busy_executors(&node.name) < node.executors
+            })
+            .map(|node| node.name.clone())
+    }
+
+    fn enqueue(&mut self, job: &str) -> u32 {
+        self.job_mut(job);
+        let id = self.next_queue_id;
+        self.next_queue_id += 1;
+        let in_queue_since = self.tick_clock();
+        self.queue.push(QueueItem {
+            id,
+            job: job.to_string(),
+            polls: 0,
+            in_queue_since,
+            build: None,
+            cancelled: false,
+            parameters: vec![],
+            script: None,
+        });
+        id
+    }
+
+    fn poll_queue_item(&mut self, id: u32) {
+        let (job, polls) = match self
+            .queue
+            .iter_mut()
+            .find(|item| item.id == id && item.build.is_none() && !item.cancelled)
+        {
+            Some(item) => {
+                item.polls += 1;
+                (item.job.clone(), item.polls)
+            }
+            None => return,
+        };
+        if polls <= self.job_mut(&job).lifecycle.queued_polls {
+            return;
+        }
+        let node = match self.free_node() {
+            Some(node) => node,
+            None => return,
+        };
+
+        let timestamp = self.tick_clock();
+        let (parameters, script) = match self.queue.iter().find(|item| item.id == id) {
+            Some(item) => (item.parameters.clone(), item.script.clone()),
+            None => return,
+        };
+        let job_builds = &mut self.job_mut(&job).builds;
+        let number = job_builds.len() as u32 + 1;
+        job_builds.push(Build {
+            number,
+            result: None,
+            polls: 0,
+            timestamp,
+            console: format!("Started by user anonymous\nBuilding on {}\n", node),
+            node: Some(node),
+            queue_id: id,
+            artifacts: vec![],
+            test_report: None,
+            inputs: vec![],
+            answers: vec![],
+            parameters,
+            script,
+        });
+        if let Some(item) = self.queue.iter_mut().find(|item| item.id == id) {
+            item.build = Some(number);
+        }
+    }
+
+    fn poll_build(&mut self, job: &str, number: u32) {
+        let lifecycle = self.job_mut(job).lifecycle;
+        let build = self.build_mut(job, number);
+        if build.result.is_some() || !build.inputs.is_empty() {
+            return;
+        }
+        build.polls += 1;
+        build.console += &format!("step {}\n", build.polls);
+        if build.polls > lifecycle.running_polls {
+            self.finish(job, number, lifecycle.result);
+        }
+    }
+
+    fn finish(&mut self, job: &str, number: u32, result: BuildStatus) {
+        let build = self.build_mut(job, number);
+        build.result = Some(result);
+        build.console += &format!("Finished: {}\n", status_name(result));
+    }
+
+    fn progress(&self, job: &str, build: &Build) -> u32 {
+        let running_polls = self.jobs[job].lifecycle.running_polls + 1;
+        (build.polls * 100 / running_polls).min(99)
+    }
+
+    fn build_json(&self, job: &str, build: &Build) -> Value {
+        let url = format!("{}{}/", self.job_url(job), build.number);
+        let class = match self.jobs[job].stages.is_empty() {
+            true => "hudson.model.FreeStyleBuild",
+            false => "org.jenkinsci.plugins.workflow.job.WorkflowRun",
+        };
+        json!({
+            "_class": class,
+            "url": url,
+            "number": build.number,
+            "duration": if build.result.is_some() { 1000 } else { 0 },
+            "estimatedDuration": 1000,
+            "timestamp": build.timestamp,
+            "keepLog": false,
+            "result": build.result,
+            "displayName": format!("#{}", build.number),
+            "fullDisplayName": format!("{} #{}", job.replace('/', " » "), build.number),
+            "description": null,
+            "building": build.result.is_none(),
+            "id": build.number.to_string(),
+            "queueId": build.queue_id,
+            "builtOn": build.node,
+            "actions": [cause_action(build.script.is_some()), {
+                "_class": "hudson.model.ParametersAction",
+                "parameters": build.parameters.iter().map(|(name, value)| json!({
+                    "_class": "hudson.model.StringParameterValue",
+                    "name": name,
+                    "value": value,
+                })).collect::<Vec<_>>(),
+            }],
+            "artifacts": build.artifacts.iter().map(|(path, _)| json!({
+                "displayPath": path.rsplit('/').next(),
+                "fileName": path.rsplit('/').next(),
+                "relativePath": path,
+            })).collect::<Vec<_>>(),
+            "fingerprint": build.artifacts.iter().map(|(path, content)| json!({
+                "fileName": path,
+                "hash": format!("{:x}", md5::compute(content)),
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Stages started by a Pipeline build, as (id, name, status), the current
+    /// stage moving forward as the build is polled
+    fn stages(&self, job: &str, build: &Build) -> Vec<(u32, String, &'static str)> {
+        let stages = &self.jobs[job].stages;
+        let started = match build.result {
+            Some(_) => stages.len(),
+            None => {
+                let running_polls = self.jobs[job].lifecycle.running_polls as usize + 1;
+                let polls = build.polls.saturating_sub(1) as usize;
+                (polls * stages.len() / running_polls + 1).min(stages.len())
+            }
+        };
+        stages
+            .iter()
+            .take(started)
+            .enumerate()
+            .map(|(index, stage)| {
+                let status = match build.result {
+                    _ if index + 1 < started => "SUCCESS",
+                    None if !build.inputs.is_empty() => "PAUSED_PENDING_INPUT",
+                    None => "IN_PROGRESS",
+                    Some(BuildStatus::Success) => "SUCCESS",
+                    Some(BuildStatus::Unstable) => "UNSTABLE",
+                    Some(BuildStatus::Failure) => "FAILED",
+                    Some(BuildStatus::Aborted) => "ABORTED",
+                    Some(BuildStatus::NotBuilt) => "NOT_EXECUTED",
+                };
+                (10 * (index as u32 + 1), stage.clone(), status)
+            })
+            .collect()
+    }
+
+    fn pipeline_run_json(&self, job: &str, build: &Build) -> Value {
+        let stages = self.stages(job, build);
+        let status = match stages.last() {
+            Some((_, _, status)) if build.result.is_some() => *status,
+            _ => "IN_PROGRESS",
+        };
+        json!({
+            "id": build.number.to_string(),
+            "name": format!("#{}", build.number),
+            "status": status,
+            "startTimeMillis": build.timestamp,
+            "durationMillis": if build.result.is_some() { 1000 } else { 0 },
+            "pauseDurationMillis": 0,
+            "queueDurationMillis": 0,
+            "stages": stages.iter().map(|(id, name, status)| json!({
+                "id": id.to_string(),
+                "name": name,
+                "execNode": build.node.clone().unwrap_or_default(),
+                "status": status,
+                "startTimeMillis": build.timestamp,
+                "durationMillis": 100,
+                "pauseDurationMillis": 0,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    fn short_job_json(&self, name: &str) -> Value {
+        let job = &self.jobs[name];
+        let last_completed = job.builds.iter().rev().find(|build| build.result.is_some());
+        let building = job.builds.last().map(|build| build.result.is_none());
+        let color = match job.disabled {
+            true => "disabled",
+            false => color(last_completed.and_then(|build| build.result)),
+        };
+        json!({
+            "_class": "hudson.model.FreeStyleProject",
+            "name": name.rsplit('/').next(),
+            "url": self.job_url(name),
+            "color": if building == Some(true) { format!("{}_anime", color) } else { color.to_string() },
+        })
+    }
+
+    fn job_json(&self, name: &str) -> Value {
+        let job = &self.jobs[name];
+        let short_build = |build: Option<&Build>| {
+            build.map(|build| {
+                json!({
+                    "number": build.number,
+                    "url": format!("{}{}/", self.job_url(name), build.number),
+                })
+            })
+        };
+        let last_with = |status: BuildStatus| {
+            short_build(
+                job.builds
+                    .iter()
+                    .rev()
+                    .find(|build| build.result == Some(status)),
+            )
+        };
+
+        let mut value = self.short_job_json(name);
+        let fields = json!({
+            "fullName": name,
+            "displayName": name.rsplit('/').next(),
+            "fullDisplayName": name.replace('/', " » "),
+            "buildable": !job.disabled,
+            "keepDependencies": false,
+            "nextBuildNumber": job.builds.len() + 1,
+            "inQueue": self.queue.iter().any(|item| item.job == name && item.build.is_none() && !item.cancelled),
+            "actions": [],
+            "firstBuild": short_build(job.builds.first()),
+            "lastBuild": short_build(job.builds.last()),
+            "lastCompletedBuild": short_build(job.builds.iter().rev().find(|build| build.result.is_some())),
+            "lastSuccessfulBuild": last_with(BuildStatus::Success),
+            "lastStableBuild": last_with(BuildStatus::Success),
+            "lastUnstableBuild": last_with(BuildStatus::Unstable),
+            "lastFailedBuild": last_with(BuildStatus::Failure),
+            "lastUnsuccessfulBuild": short_build(job.builds.iter().rev().find(|build| {
+                build.result.is_some() && build.result != Some(BuildStatus::Success)
+            })),
+            "builds": job.builds.iter().rev().map(|build| self.build_json(name, build)).collect::<Vec<_>>(),
+            "healthReport": [],
+            "queueItem": null,
+            "property": [{ "parameterDefinitions": job.parameters }],
+        });
+        if let (Value::Object(value), Value::Object(fields)) = (&mut value, fields) {
+            value.extend(fields);
+        }
+        value
+    }
+
+    /// Jobs and folders directly in the folder `prefix`, with their children
+    fn jobs_tree(&self, prefix: &str) -> Vec<Value> {
+        let mut children = Vec::<String>::new();
+        for name in self.jobs.keys() {
+            if let Some(rest) = name.strip_prefix(prefix) {
+                let child = format!("{}{}", prefix, rest.split('/').next().unwrap_or_default());
+                if !children.contains(&child) {
+                    children.push(child);
+                }
+            }
+        }
+        children
+            .iter()
+            .map(|child| {
+                if self.jobs.contains_key(child) {
+                    self.short_job_json(child)
+                } else {
+                    json!({
+                        "_class": "com.cloudbees.hudson.plugins.folder.Folder",
+                        "name": child.rsplit('/').next(),
+                        "url": self.job_url(child),
+                        "jobs": self.jobs_tree(&format!("{}/", child)),
+                    })
+                }
+            })
+            .collect()
+    }
+
+    fn queue_item_json(&self, item: &QueueItem) -> Value {
+        let waiting = item.build.is_none() && !item.cancelled;
+        json!({
+            "_class": if waiting { "hudson.model.Queue$WaitingItem" } else { "hudson.model.Queue$LeftItem" },
+            "blocked": false,
+            "buildable": waiting,
+            "cancelled": item.cancelled,
+            "id": item.id,
+            "inQueueSince": item.in_queue_since,
+            "params": "",
+            "stuck": false,
+            "task": self.short_job_json(&item.job),
+            "url": format!("queue/item/{}/", item.id),
+            "why": if waiting { Some("Waiting for next available executor") } else { None },
+            "buildableStartMilliseconds": item.in_queue_since,
+            "executable": item.build.map(|number| json!({
+                "number": number,
+                "url": format!("{}{}/", self.job_url(&item.job), number),
+            })),
+            "actions": [cause_action(item.script.is_some())],
+        })
+    }
+
+    fn computers_json(&self) -> Value {
+        let computers = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let running = |pipeline: bool| {
+                    self.jobs
+                        .iter()
+                        .filter(move |(_, job)| job.stages.is_empty() != pipeline)
+                        .flat_map(|(name, job)| job.builds.iter().map(move |build| (name, build)))
+                        .filter(|(_, build)| build.result.is_none())
+                        .map(|(name, build)| {
+                            (
+                                build,
+                                json!({
+                                    "progress": self.progress(name, build),
+                                    "currentExecutable": self.build_json(name, build),
+                                }),
+                            )
+                        })
+                };
+                let mut executors = running(false)
+                    .filter(|(build, _)| build.node.as_deref() == Some(&node.name))
+                    .map(|(_, executor)| executor)
+                    .collect::<Vec<_>>();
+                // Pipeline builds run on flyweight executors of the master,
+                // their `node` steps are not listed as builds
+                let one_off_executors: Vec<Value> = match node.name.as_str() {
+                    "master" => running(true).map(|(_, executor)| executor).collect(),
+                    _ => vec![],
+                };
+                while executors.len() < node.executors {
+                    executors.push(json!({ "progress": -1, "currentExecutable": null }));
+                }
+                for executor in executors.iter_mut() {
+                    executor["idle"] = json!(executor["currentExecutable"].is_null());
+                }
+                let idle = executors.iter().all(|executor| executor["idle"] == true);
+                json!({
+                    "_class": if node.name == "master" {
+                        "hudson.model.Hudson$MasterComputer"
+                    } else {
+                        "hudson.slaves.SlaveComputer"
+                    },
+                    "displayName": node.name,
+                    "description": "",
+                    "idle": idle,
+                    "idleStartMilliseconds": self.clock,
+                    "numExecutors": node.executors,
+                    "oneOffExecutors": one_off_executors,
+                    "offline": node.offline_reason.is_some(),
+                    "temporarilyOffline": node.offline_reason.is_some(),
+                    "offlineCause": node.offline_reason.as_ref().map(|_| json!({
+                        "_class": "hudson.slaves.OfflineCause$UserCause",
+                    })),
+                    "offlineCauseReason": node.offline_reason.clone().unwrap_or_default(),
+                    "assignedLabels": node
+                        .labels
+                        .iter()
+                        .chain(std::iter::once(&node.name))
+                        .map(|label| json!({ "name": label }))
+                        .collect::<Vec<_>>(),
+                    "monitorData": {
+                        "hudson.node_monitors.ArchitectureMonitor": "Linux (amd64)",
+                        "hudson.node_monitors.DiskSpaceMonitor": {
+                            "_class": "hudson.node_monitors.DiskSpaceMonitorDescriptor$DiskSpace",
+                            "path": "/var/jenkins",
+                            "size": 10_737_418_240_u64,
+                        },
+                        "hudson.node_monitors.SwapSpaceMonitor": {
+                            "_class": "hudson.node_monitors.SwapSpaceMonitor$MemoryUsage2",
+                            "availablePhysicalMemory": 2_147_483_648_u64,
+                            "availableSwapSpace": 0,
+                            "totalPhysicalMemory": 8_589_934_592_u64,
+                            "totalSwapSpace": 0,
+                        },
+                    },
+                    "executors": executors,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({ "_class": "hudson.model.ComputerSet", "computer": computers })
+    }
+
+    fn view_json(&self, name: &str) -> Option<Value> {
+        let jobs = match self.views.get(name) {
+            Some(jobs) => jobs.clone(),
+            None if name == "all" => self.jobs.keys().cloned().collect(),
+            None => return None,
+        };
+        Some(json!({
+            "_class": "hudson.model.ListView",
+            "name": name,
+            "url": format!("{}/view/{}/", self.url, urlencoding::encode(name)),
+            "description": null,
+            "jobs": jobs.iter().filter(|job| self.jobs.contains_key(*job)).map(|job| self.short_job_json(job)).collect::<Vec<_>>(),
+            "property": [],
+        }))
+    }
+
+    fn views_json(&self) -> Vec<Value> {
+        std::iter::once("all")
+            .chain(
+                self.views
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|view| *view != "all"),
+            )
+            .map(|view| {
+                json!({
+                    "name": view,
+                    "url": format!("{}/view/{}/", self.url, urlencoding::encode(view)),
+                })
+            })
+            .collect()
+    }
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query_params(query)
+        .into_iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+/// Decoded pairs of a query string or of an url-encoded form
+fn query_params(query: &str) -> Vec<(String, String)> {
+    let decode = |value: &str| {
+        let value = value.replace('+', " ");
+        urlencoding::decode(&value)
+            .map(|value| value.to_string())
+            .unwrap_or(value)
+    };
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let mut pair = pair.splitn(2, '=');
+            Some((decode(pair.next()?), decode(pair.next()?)))
+        })
+        .collect()
+}
+
+fn multipart_fields(body: &str, boundary: &str) -> Vec<(String, String)> {
+    body.split(boundary)
+        .filter_map(|part| {
+            let pos = part.find("\r\n\r\n")?;
+            let headers = &part[..pos];
+            let name_pos = headers.find("name=\"")? + "name=\"".len();
+            let name = &headers[name_pos..];
+            let name = &name[..name.find('"')?];
+            let value = part[pos + 4..].strip_suffix("\r\n")?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Answer from the mock server
+enum Reply {
+    Json(Value),
+    Text(String, Vec<(&'static str, String)>),
+    Bytes(Vec<u8>, u16),
+    Created(String),
+    Status(u16),
+}
+
+fn handle(state: &Mutex<State>, mut request: Request) {
+    let range = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Range"))
+        .and_then(|header| {
+            header
+                .value
+                .as_str()
+                .trim_start_matches("bytes=")
+                .trim_end_matches('-')
+                .parse::<usize>()
+                .ok()
+        });
+
+    let mut body = String::new();
+    let _ = request.as_reader().read_to_string(&mut body);
+    // fields of a multipart form are routed like an url encoded form, with
+    // the content of the uploaded files as values
+    let boundary = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Content-Type"))
+        .and_then(|header| {
+            let value = header.value.as_str();
+            value
+                .find("boundary=")
+                .map(|pos| format!("--{}", &value[pos + "boundary=".len()..]))
+        });
+    if let Some(boundary) = boundary {
+        body = multipart_fields(&body, &boundary)
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    urlencoding::encode(name),
+                    urlencoding::encode(value)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+    }
+
+    let reply = {
+        let mut state = state.lock().expect("mock Jenkins state poisoned");
+        state
+            .requests
+            .push(format!("{} {}", request.method(), request.url()));
+        state.authorization = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .map(|header| header.value.to_string());
+        let url = request.url().strip_prefix(state.context_path.as_str());
+        match url {
+            Some(url) if url.starts_with("/queue/item/") && state.failing_polls > 0 => {
+                state.failing_polls -= 1;
+                Reply::Status(503)
+            }
+            Some(url)
+                if state
+                    .failing_paths
+                    .iter()
+                    .any(|path| url.split('?').next() == Some(path.as_str())) =>
+            {
+                Reply::Status(500)
+            }
+            Some(url) if url.starts_with('/') => {
+                let url = url.to_string();
+                route(&mut state, request.method(), &url, range, body)
+            }
+            _ => Reply::Status(404),
+        }
+    };
+
+    let json_header =
+        Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("invalid header");
+    let _ = match reply {
+        Reply::Json(value) => {
+            request.respond(Response::from_string(value.to_string()).with_header(json_header))
+        }
+        Reply::Text(text, headers) => request.respond(headers.into_iter().fold(
+            Response::from_string(text),
+            |response, (name, value)| {
+                response.with_header(
+                    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("invalid header"),
+                )
+            },
+        )),
+        Reply::Bytes(bytes, status) => {
+            request.respond(Response::from_data(bytes).with_status_code(status))
+        }
+        Reply::Created(location) => request.respond(Response::empty(201).with_header(
+            Header::from_bytes(&b"Location"[..], location.as_bytes()).expect("invalid header"),
+        )),
+        Reply::Status(status) => request.respond(Response::empty(status)),
+    };
+}
+
+fn route(
+    state: &mut State,
+    method: &Method,
+    url: &str,
+    range: Option<usize>,
+    body: String,
+) -> Reply {
+    let (path, query) = match url.find('?') {
+        Some(pos) => (&url[..pos], &url[pos + 1..]),
+        None => (url, ""),
+    };
+    let mut segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            urlencoding::decode(segment)
+                .map(|segment| segment.to_string())
+                .unwrap_or_else(|_| segment.to_string())
+        })
+        .collect::<Vec<_>>();
+    let api = segments.ends_with(&["api".to_string(), "json".to_string()]);
+    if api {
+        segments.truncate(segments.len() - 2);
+    }
+
+    let mut job = vec![];
+    while segments.len() >= 2 && segments[0] == "job" {
+        job.push(segments[1].clone());
+        segments.drain(..2);
+    }
+    let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+
+    if *method == Method::Post && segments == ["createItem"] {
+        let name = match query_param(query, "name") {
+            Some(name) => job
+                .iter()
+                .cloned()
+                .chain(Some(name))
+                .collect::<Vec<_>>()
+                .join("/"),
+            None => return Reply::Status(400),
+        };
+        if state.jobs.contains_key(&name) {
+            return Reply::Status(400);
+        }
+        let config = match query_param(query, "mode").as_deref() {
+            Some("copy") => {
+                let from = query_param(query, "from").unwrap_or_default();
+                match state.jobs.get(from.trim_start_matches('/')) {
+                    Some(job) => job.config_xml(),
+                    None => return Reply::Status(400),
+                }
+            }
+            _ => body,
+        };
+        state.jobs.insert(
+            name,
+            Job {
+                config: Some(config),
+                ..Job::default()
+            },
+        );
+        return Reply::Status(200);
+    }
+    if segments == ["config.xml"] && !job.is_empty() {
+        return match state.jobs.get_mut(&job.join("/")) {
+            Some(job) if *method == Method::Post => {
+                job.config = Some(body);
+                Reply::Status(200)
+            }
+            Some(job) => Reply::Text(job.config_xml(), vec![]),
+            None => Reply::Status(404),
+        };
+    }
+
+    if !job.is_empty() {
+        return route_job(
+            state,
+            method,
+            &job.join("/"),
+            &segments,
+            api,
+            query,
+            range,
+            &body,
+        );
+    }
+
+    match (method, segments.as_slice(), api) {
+        (Method::Get, [], true) => Reply::Json(json!({
+            "_class": "hudson.model.Hudson",
+            "mode": "NORMAL",
+            "nodeDescription": "the master Jenkins node",
+            "nodeName": "",
+            "numExecutors": 2,
+            "description": null,
+            "jobs": state.jobs_tree(""),
+            "views": state.views_json(),
+        })),
+        (Method::Get, ["me"], true) => Reply::Json(json!({
+            "fullName": "anonymous",
+            "absoluteUrl": format!("{}/user/anonymous", state.url),
+        })),
+        (Method::Get, ["computer"], true) => Reply::Json(state.computers_json()),
+        (Method::Post, ["computer", name, "toggleOffline"], false) => {
+            let reason = query_param(query, "offlineMessage").unwrap_or_default();
+            match state.node_mut(name) {
+                Some(node) => {
+                    node.offline_reason = match node.offline_reason {
+                        Some(_) => None,
+                        None => Some(reason),
+                    };
+                    Reply::Status(204)
+                }
+                None => Reply::Status(404),
+            }
+        }
+        (Method::Post, ["computer", name, "changeOfflineCause"], false) => {
+            let reason = query_param(query, "offlineMessage").unwrap_or_default();
+            match state.node_mut(name) {
+                Some(node) if node.offline_reason.is_some() => {
+                    node.offline_reason = Some(reason);
+                    Reply::Status(204)
+                }
+                _ => Reply::Status(404),
+            }
+        }
+        (Method::Get, ["queue"], true) => Reply::Json(json!({
+            "items": state
+                .queue
+                .iter()
+                .filter(|item| item.build.is_none() && !item.cancelled)
+                .map(|item| state.queue_item_json(item))
+                .collect::<Vec<_>>(),
+        })),
+        (Method::Get, ["queue", "item", id], true) => match id.parse() {
+            Ok(id) => {
+                state.poll_queue_item(id);
+                match state.queue.iter().find(|item| item.id == id) {
+                    Some(item) => Reply::Json(state.queue_item_json(item)),
+                    None => Reply::Status(404),
+                }
+            }
+            Err(_) => Reply::Status(404),
+        },
+        (Method::Post, ["queue", "cancelItem"], false) => {
+            let id = query_param(query, "id").and_then(|id| id.parse::<u32>().ok());
+            match state.queue.iter_mut().find(|item| Some(item.id) == id) {
+                Some(item) => {
+                    item.cancelled = true;
+                    Reply::Status(204)
+                }
+                None => Reply::Status(404),
+            }
+        }
+        (Method::Get, ["view", name], true) => match state.view_json(name) {
+            Some(view) => Reply::Json(view),
+            None => Reply::Status(404),
+        },
+        _ => Reply::Status(404),
+    }
+}
+
+/// Action with the cause of a build, a user or a replay
+fn cause_action(replayed: bool) -> Value {
+    let cause = match replayed {
+        true => json!({
+            "_class": "org.jenkinsci.plugins.workflow.cps.replay.ReplayCause",
+            "shortDescription": "Replayed",
+        }),
+        false => json!({
+            "_class": "hudson.model.Cause$UserIdCause",
+            "shortDescription": "Started by user anonymous",
+            "userId": "anonymous",
+            "userName": "anonymous",
+        }),
+    };
+    json!({
+        "_class": "hudson.model.CauseAction",
+        "causes": [cause],
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn route_job(
+    state: &mut State,
+    method: &Method,
+    name: &str,
+    segments: &[&str],
+    api: bool,
+    query: &str,
+    range: Option<usize>,
+    body: &str,
+) -> Reply {
+    if !state.jobs.contains_key(name) {
+        return Reply::Status(404);
+    }
+
+    let (build, rest) = match segments.split_first() {
+        None if api && *method == Method::Get => return Reply::Json(state.job_json(name)),
+        // like Jenkins, building a parameterized job without its parameters
+        // only shows the form to fill them
+        Some((&"build", []))
+            if *method == Method::Post && !state.jobs[name].parameters.is_empty() =>
+        {
+            return Reply::Status(400);
+        }
+        Some((&"build", [])) | Some((&"buildWithParameters", [])) if *method == Method::Post => {
+            let mut parameters = query_params(body);
+            for definition in &state.jobs[name].parameters {
+                let default = &definition["defaultParameterValue"]["value"];
+                match (definition["name"].as_str(), default.as_str()) {
+                    (Some(name), Some(default))
+                        if !parameters.iter().any(|(given, _)| given == name) =>
+                    {
+                        parameters.push((name.to_string(), default.to_string()));
+                    }
+                    _ => (),
+                }
+            }
+            parameters.sort();
+            let id = state.enqueue(name);
+            if let Some(item) = state.queue.last_mut() {
+                item.parameters = parameters;
+            }
+            return Reply::Created(format!("{}/queue/item/{}/", state.url, id));
+        }
+        Some((&"enable", [])) | Some((&"disable", [])) if *method == Method::Post => {
+            state.job_mut(name).disabled = segments[0] == "disable";
+            return Reply::Status(200);
+        }
+        Some((&"doDelete", [])) if *method == Method::Post => {
+            state.jobs.remove(name);
+            return Reply::Status(200);
+        }
+        Some((&"confirmRename", [])) if *method == Method::Post => {
+            let new_name = match (name.rfind('/'), query_param(query, "newName")) {
+                (_, None) => return Reply::Status(400),
+                (Some(pos), Some(new_name)) => format!("{}/{}", &name[..pos], new_name),
+                (None, Some(new_name)) => new_name,
+            };
+            if let Some(job) = state.jobs.remove(name) {
+                state.jobs.insert(new_name, job);
+            }
+            return Reply::Status(200);
+        }
+        Some((build, rest)) => (*build, rest),
+        None => return Reply::Status(404),
+    };
+
+    let builds = &state.jobs[name].builds;
+    let number = match build {
+        "lastBuild" => builds.last(),
+        "lastCompletedBuild" => builds.iter().rev().find(|build| build.result.is_some()),
+        "lastSuccessfulBuild" => builds
+            .iter()
+            .rev()
+            .find(|build| build.result == Some(BuildStatus::Success)),
+        "lastFailedBuild" => builds
+            .iter()
+            .rev()
+            .find(|build| build.result == Some(BuildStatus::Failure)),
+        number => number
+            .parse::<u32>()
+            .ok()
+            .and_then(|number| builds.iter().find(|build| build.number == number)),
+    }
+    .map(|build| build.number);
+    let number = match number {
+        Some(number) => number,
+        None => return Reply::Status(404),
+    };
+
+    match (method, rest, api) {
+        (Method::Get, [], true) => {
+            state.poll_build(name, number);
+            Reply::Json(state.build_json(name, state.build(name, number)))
+        }
+        (Method::Get, ["consoleText"], false) => {
+            Reply::Text(state.build_mut(name, number).console.clone(), vec![])
+        }
+        (Method::Get, ["logText", "progressiveText"], false) => {
+            // a followed build moves on between two reads of its log
+            state.poll_build(name, number);
+            let build = state.build_mut(name, number);
+            let start = query_param(query, "start")
+                .and_then(|start| start.parse().ok())
+                .unwrap_or(0)
+                .min(build.console.len());
+            Reply::Text(
+                build.console[start..].to_string(),
+                vec![
+                    ("X-Text-Size", build.console.len().to_string()),
+                    ("X-More-Data", build.result.is_none().to_string()),
+                ],
+            )
+        }
+        (Method::Get, ["testReport"], true) => {
+            match state.build_mut(name, number).test_report.clone() {
+                Some(report) => Reply::Json(report),
+                None => Reply::Status(404),
+            }
+        }
+        (Method::Get, ["artifact", ..], false) | (Method::Head, ["artifact", ..], false) => {
+            let path = rest[1..].join("/");
+            let build = state.build_mut(name, number);
+            match build
+                .artifacts
+                .iter()
+                .find(|(artifact, _)| *artifact == path)
+            {
+                Some((_, content)) => match range {
+                    Some(start) if start >= content.len() => Reply::Status(416),
+                    Some(start) => Reply::Bytes(content[start..].to_vec(), 206),
+                    None => Reply::Bytes(content.clone(), 200),
+                },
+                None => Reply::Status(404),
+            }
+        }
+        (Method::Get, ["wfapi", "describe"], false) if !state.jobs[name].stages.is_empty() => {
+            Reply::Json(state.pipeline_run_json(name, state.build(name, number)))
+        }
+        // each stage has a single step, with the node id following the one of the stage
+        (Method::Get, ["execution", "node", id, "wfapi", "describe"], false) => {
+            let build = state.build(name, number);
+            match state
+                .stages(name, build)
+                .into_iter()
+                .find(|(stage, _, _)| stage.to_string() == *id)
+            {
+                Some((stage, _, status)) => Reply::Json(json!({
+                    "id": id,
+                    "status": status,
+                    "stageFlowNodes": [{
+                        "id": (stage + 1).to_string(),
+                        "name": "Shell Script",
+                        "status": status,
+                        "_links": {
+                            "log": { "href": format!("execution/node/{}/wfapi/log", stage + 1) },
+                        },
+                    }],
+                })),
+                None => Reply::Status(404),
+            }
+        }
+        (Method::Get, ["execution", "node", id, "wfapi", "log"], false) => {
+            let build = state.build(name, number);
+            match state
+                .stages(name, build)
+                .into_iter()
+                .find(|(stage, _, _)| (stage + 1).to_string() == *id)
+            {
+                Some((_, stage, _)) => Reply::Json(json!({
+                    "nodeId": id,
+                    "nodeStatus": "SUCCESS",
+                    "text": format!(
+                        "<span class=\"pipeline-node-{}\">+ make {}\n&lt;{}&gt; done\n</span>",
+                        id, stage, stage
+                    ),
+                })),
+                None => Reply::Status(404),
+            }
+        }
+        // replaying keeps the parameters of the build
+        (Method::Post, ["replay", "run"], false) if !state.jobs[name].stages.is_empty() => {
+            let script = match query_param(body, "json")
+                .and_then(|json| serde_json::from_str::<Value>(&json).ok())
+                .and_then(|json| json["mainScript"].as_str().map(ToString::to_string))
+            {
+                Some(script) => script,
+                None => return Reply::Status(400),
+            };
+            let parameters = state.build(name, number).parameters.clone();
+            state.enqueue(name);
+            if let Some(item) = state.queue.last_mut() {
+                item.parameters = parameters;
+                item.script = Some(script);
+            }
+            Reply::Status(200)
+        }
+        (Method::Get, ["wfapi", "pendingInputActions"], false) => {
+            Reply::Json(Value::from(state.build(name, number).inputs.clone()))
+        }
+        (Method::Post, ["input", id, answer], false) => {
+            let build = state.build_mut(name, number);
+            let position = match build.inputs.iter().position(|input| input["id"] == *id) {
+                Some(position) => position,
+                None => return Reply::Status(404),
+            };
+            let parameters = match *answer {
+                "proceedEmpty" => Value::Null,
+                "submit" => match query_param(body, "json")
+                    .and_then(|json| serde_json::from_str::<Value>(&json).ok())
+                {
+                    Some(json) => json["parameter"].clone(),
+                    None => return Reply::Status(400),
+                },
+                "abort" => {
+                    build.inputs.remove(position);
+                    state.finish(name, number, BuildStatus::Aborted);
+                    return Reply::Status(200);
+                }
+                _ => return Reply::Status(404),
+            };
+            build.inputs.remove(position);
+            build.answers.push((id.to_string(), parameters));
+            Reply::Status(200)
+        }
+        (Method::Post, [signal], false) if ["stop", "term", "kill"].contains(signal) => {
+            if state.build_mut(name, number).result.is_none() {
+                state.finish(name, number, BuildStatus::Aborted);
+            }
+            Reply::Status(200)
+        }
+        _ => Reply::Status(404),
+    }
+}
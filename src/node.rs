@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A computer of Jenkins, the master or an agent
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Node {
+    pub name: String,
+    pub description: Option<String>,
+    pub online: bool,
+    /// put offline by a user, and not because it is disconnected
+    pub temporarily_offline: bool,
+    pub offline_cause: Option<String>,
+    pub labels: Vec<String>,
+    pub executors: u32,
+    pub busy_executors: u32,
+    /// timestamp since which all executors are idle, if they are
+    pub idle_since: Option<i64>,
+    /// free space of the disk holding the workspaces, in bytes
+    pub disk_space: Option<u64>,
+    /// free space of the temporary directory, in bytes
+    pub temp_space: Option<u64>,
+    pub available_swap_space: Option<u64>,
+    pub total_swap_space: Option<u64>,
+    pub available_physical_memory: Option<u64>,
+    pub total_physical_memory: Option<u64>,
+    pub architecture: Option<String>,
+    /// average response time of the node, in milliseconds
+    pub response_time: Option<u64>,
+    /// difference between the clocks of the node and the master, in milliseconds
+    pub clock_difference: Option<i64>,
+    /// name of the node in urls, `(master)` for the master
+    #[serde(skip)]
+    pub(crate) url_name: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Computers {
+    pub(crate) computer: Vec<Computer>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Computer {
+    #[serde(rename = "_class")]
+    class: Option<String>,
+    display_name: String,
+    description: Option<String>,
+    idle: bool,
+    idle_start_milliseconds: Option<i64>,
+    num_executors: u32,
+    offline: bool,
+    offline_cause: Option<OfflineCause>,
+    offline_cause_reason: Option<String>,
+    temporarily_offline: bool,
+    #[serde(default)]
+    monitor_data: HashMap<String, Value>,
+    #[serde(default)]
+    executors: Vec<Executor>,
+    #[serde(default)]
+    assigned_labels: Vec<jenkins_api::nodes::computer::AssignedLabel>,
+}
+
+#[derive(Deserialize)]
+struct OfflineCause {
+    #[serde(rename = "_class")]
+    class: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Executor {
+    idle: bool,
+}
+
+impl From<Computer> for Node {
+    fn from(computer: Computer) -> Self {
+        let Computer {
+            class,
+            display_name,
+            description,
+            idle,
+            idle_start_milliseconds,
+            num_executors,
+            offline,
+            offline_cause,
+            offline_cause_reason,
+            temporarily_offline,
+            monitor_data,
+            executors,
+            assigned_labels,
+        } = computer;
+        let monitor = |name: &str| monitor_data.get(&format!("hudson.node_monitors.{}", name));
+        let monitor_u64 = |name: &str, field: &str| {
+            monitor(name)
+                .and_then(|data| data.get(field))
+                .and_then(Value::as_u64)
+        };
+
+        let offline_cause = match offline_cause_reason {
+            Some(reason) if !reason.is_empty() => Some(reason),
+            _ => offline_cause.and_then(|cause| cause.class).map(|class| {
+                match class.rfind(['$', '.']) {
+                    Some(pos) => class[pos + 1..].to_string(),
+                    None => class,
+                }
+            }),
+        };
+        let master = class.as_deref() == Some("hudson.model.Hudson$MasterComputer")
+            || display_name == "master";
+        let url_name = match display_name.as_str() {
+            "Built-In Node" if master => "(built-in)".to_string(),
+            _ if master => "(master)".to_string(),
+            name => name.to_string(),
+        };
+
+        Node {
+            online: !offline,
+            temporarily_offline,
+            offline_cause: offline_cause.filter(|_| offline),
+            // Jenkins gives each node its own name as a label
+            labels: assigned_labels
+                .into_iter()
+                .map(|label| label.name)
+                .filter(|label| *label != display_name)
+                .collect(),
+            executors: num_executors,
+            busy_executors: executors.iter().filter(|executor| !executor.idle).count() as u32,
+            idle_since: idle_start_milliseconds.filter(|_| idle),
+            disk_space: monitor_u64("DiskSpaceMonitor", "size"),
+            temp_space: monitor_u64("TemporarySpaceMonitor", "size"),
+            available_swap_space: monitor_u64("SwapSpaceMonitor", "availableSwapSpace"),
+            total_swap_space: monitor_u64("SwapSpaceMonitor", "totalSwapSpace"),
+            available_physical_memory: monitor_u64("SwapSpaceMonitor", "availablePhysicalMemory"),
+            total_physical_memory: monitor_u64("SwapSpaceMonitor", "totalPhysicalMemory"),
+            architecture: monitor("ArchitectureMonitor")
+                .and_then(Value::as_str)
+                .map(ToString::to_string),
+            response_time: monitor_u64("ResponseTimeMonitor", "average"),
+            clock_difference: monitor("ClockMonitor")
+                .and_then(|data| data.get("diff"))
+                .and_then(Value::as_i64),
+            name: display_name,
+            description: description.filter(|description| !description.is_empty()),
+            url_name,
+        }
+    }
+}
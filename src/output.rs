@@ -0,0 +1,360 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use handlebars::Handlebars;
+use log::{debug, error};
+use serde::Serialize;
+use serde_json::Value;
+
+static HANDLEBARS_TEMPLATE: &str = "item_template";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Template,
+    Json,
+    JsonArray,
+    Yaml,
+    Csv,
+    Table,
+}
+
+impl OutputFormat {
+    pub fn variants() -> &'static [&'static str] {
+        &["template", "json", "json-array", "yaml", "csv", "table"]
+    }
+
+    /// Formats written as a whole document by each call to `Output::items`, that
+    /// can not be combined with other output
+    pub fn is_document(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::JsonArray | OutputFormat::Yaml | OutputFormat::Csv | OutputFormat::Table
+        )
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "template" => Ok(OutputFormat::Template),
+            "json" => Ok(OutputFormat::Json),
+            "json-array" => Ok(OutputFormat::JsonArray),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(failure::format_err!("unknown output format {}", s)),
+        }
+    }
+}
+
+/// Conditions on the fields of rendered items, remembered once one of them is met
+#[derive(Debug, Clone, Default)]
+pub struct Until {
+    conditions: Vec<(String, String)>,
+    results: Vec<String>,
+    met: Rc<Cell<bool>>,
+}
+
+impl Until {
+    /// Conditions as pairs of a field path, like `build.result`, and its expected value,
+    /// and results expected for the build shown first, the newest one
+    pub fn new(conditions: Vec<(String, String)>, results: Vec<String>) -> Self {
+        Until {
+            conditions,
+            results,
+            met: Rc::default(),
+        }
+    }
+
+    pub fn is_met(&self) -> bool {
+        self.met.get()
+    }
+
+    fn check(&self, value: &Value, first: bool) {
+        let has_result = |result: &String| {
+            // builds are either rendered directly, or enriched under `build`
+            ["result", "build.result"]
+                .iter()
+                .any(|field| cell(value, field).eq_ignore_ascii_case(result))
+        };
+        if self
+            .conditions
+            .iter()
+            .any(|(field, expected)| cell(value, field).eq_ignore_ascii_case(expected))
+            || (first && self.results.iter().any(has_result))
+        {
+            self.met.set(true);
+        }
+    }
+}
+
+/// Render items either through the handlebars template of the command or
+/// in a structured format
+pub struct Output {
+    render: Handlebars<'static>,
+    format: OutputFormat,
+    columns: Option<Vec<String>>,
+    until: Until,
+}
+
+impl Output {
+    pub fn new(
+        render: Handlebars<'static>,
+        format: OutputFormat,
+        columns: Option<Vec<String>>,
+    ) -> Self {
+        Output {
+            render,
+            format,
+            columns,
+            until: Until::default(),
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn until(&mut self, until: Until) {
+        self.until = until;
+    }
+
+    pub fn template(&mut self, template: String) -> Result<(), failure::Error> {
+        self.render
+            .register_template_string(HANDLEBARS_TEMPLATE, template)?;
+        Ok(())
+    }
+
+    pub fn items<T, IT>(&mut self, items: T) -> impl Iterator<Item = String>
+    where
+        T: Iterator<Item = IT>,
+        IT: Serialize,
+    {
+        let until = self.until.clone();
+        let values = items
+            .filter_map(|item| {
+                serde_json::to_value(&item)
+                    .map_err(|err| error!("{}", err))
+                    .ok()
+            })
+            .inspect(|value| debug!("{}", value))
+            .enumerate()
+            .inspect(move |(index, value)| until.check(value, *index == 0))
+            .map(|(_, value)| value);
+
+        match self.format {
+            OutputFormat::Template => {
+                let render = &self.render;
+                values
+                    .filter_map(|value| {
+                        render
+                            .render(HANDLEBARS_TEMPLATE, &value)
+                            .map(|s| s.replace("\\t", "\t"))
+                            .map(|s| s.replace("\\n", "\n"))
+                            .map_err(|err| error!("{}", err))
+                            .ok()
+                    })
+                    .collect::<Vec<_>>()
+            }
+            OutputFormat::Json => values.map(|value| value.to_string()).collect(),
+            OutputFormat::JsonArray => {
+                to_string(serde_json::to_string_pretty(&values.collect::<Vec<_>>()))
+            }
+            OutputFormat::Yaml => to_string(serde_yaml::to_string(&values.collect::<Vec<_>>())),
+            OutputFormat::Csv => {
+                let values = values.collect::<Vec<_>>();
+                to_string(to_csv(&self.columns_of(&values), &values))
+            }
+            OutputFormat::Table => {
+                let values = values.collect::<Vec<_>>();
+                to_table(&self.columns_of(&values), &values)
+            }
+        }
+        .into_iter()
+    }
+
+    fn columns_of(&self, values: &[Value]) -> Vec<String> {
+        match self.columns {
+            Some(ref columns) => columns.clone(),
+            None => {
+                let mut columns = vec![];
+                for value in values {
+                    leaf_paths(None, value, &mut columns);
+                }
+                columns
+            }
+        }
+    }
+}
+
+fn to_string<E: std::fmt::Display>(result: Result<String, E>) -> Vec<String> {
+    match result {
+        Ok(string) => vec![string.trim_end().to_string()],
+        Err(err) => {
+            error!("{}", err);
+            vec![]
+        }
+    }
+}
+
+/// Dotted paths to all scalar values of `value`, in order of first appearance
+fn leaf_paths(prefix: Option<&str>, value: &Value, paths: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = match prefix {
+                    Some(prefix) => format!("{}.{}", prefix, key),
+                    None => key.clone(),
+                };
+                leaf_paths(Some(&path), value, paths);
+            }
+        }
+        Value::Array(_) => (),
+        _ => {
+            if let Some(prefix) = prefix {
+                if !paths.iter().any(|path| path == prefix) {
+                    paths.push(prefix.to_string());
+                }
+            }
+        }
+    }
+}
+
+fn cell(value: &Value, column: &str) -> String {
+    match column
+        .split('.')
+        .try_fold(value, |value, key| value.get(key))
+    {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(string)) => string.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn to_csv(columns: &[String], values: &[Value]) -> Result<String, failure::Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(columns)?;
+    for value in values {
+        writer.write_record(columns.iter().map(|column| cell(value, column)))?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn to_table(columns: &[String], values: &[Value]) -> Vec<String> {
+    let mut rows = vec![columns
+        .iter()
+        .map(|column| column.to_uppercase())
+        .collect::<Vec<_>>()];
+    rows.extend(values.iter().map(|value| {
+        columns
+            .iter()
+            .map(|column| cell(value, column).replace('\n', " "))
+            .collect::<Vec<_>>()
+    }));
+    let widths = (0..columns.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(format: OutputFormat, columns: Option<&[&str]>, items: Vec<Value>) -> Vec<String> {
+        let columns = columns.map(|columns| columns.iter().map(|c| c.to_string()).collect());
+        let mut output = Output::new(Handlebars::new(), format, columns);
+        output
+            .template("{{ name }}\\t{{ build.result }}".to_string())
+            .unwrap();
+        output.items(items.into_iter()).collect()
+    }
+
+    fn jobs() -> Vec<Value> {
+        vec![
+            json!({ "name": "app", "build": { "number": 2, "result": "SUCCESS" } }),
+            json!({ "name": "api", "build": { "number": 10, "result": null }, "tags": ["a"] }),
+        ]
+    }
+
+    #[test]
+    fn template_and_json_render_each_item() {
+        assert_eq!(
+            render(OutputFormat::Template, None, jobs()),
+            vec!["app\tSUCCESS", "api\t"]
+        );
+        let lines = render(OutputFormat::Json, None, jobs());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(serde_json::from_str::<Value>(&lines[1]).unwrap(), jobs()[1]);
+    }
+
+    #[test]
+    fn documents_hold_all_items() {
+        let lines = render(OutputFormat::JsonArray, None, jobs());
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            serde_json::from_str::<Value>(&lines[0]).unwrap(),
+            Value::Array(jobs())
+        );
+
+        let lines = render(OutputFormat::Yaml, None, jobs());
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            serde_yaml::from_str::<Value>(&lines[0]).unwrap(),
+            Value::Array(jobs())
+        );
+    }
+
+    #[test]
+    fn csv_and_table_columns_default_to_scalar_fields() {
+        assert_eq!(
+            render(OutputFormat::Csv, None, jobs()),
+            vec!["build.number,build.result,name\n2,SUCCESS,app\n10,,api"]
+        );
+        assert_eq!(
+            render(OutputFormat::Table, Some(&["build.number", "name"]), jobs()),
+            vec![
+                "BUILD.NUMBER  NAME",
+                "2             app",
+                "10            api"
+            ]
+        );
+    }
+
+    #[test]
+    fn until_is_met_by_a_field_or_the_first_result() {
+        let mut output = Output::new(Handlebars::new(), OutputFormat::Json, None);
+        let until = Until::new(vec![("name".to_string(), "API".to_string())], vec![]);
+        output.until(until.clone());
+        output.items(jobs().into_iter()).for_each(drop);
+        assert!(until.is_met());
+
+        // only the first, newest, build counts for the result
+        let until = Until::new(vec![], vec!["success".to_string()]);
+        output.until(until.clone());
+        output.items(jobs().into_iter().rev()).for_each(drop);
+        assert!(!until.is_met());
+        output.items(jobs().into_iter()).for_each(drop);
+        assert!(until.is_met());
+    }
+}
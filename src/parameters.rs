@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Value given to a parameter when triggering a build
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterValue {
+    /// sent as is to Jenkins
+    Value(String),
+    /// local file uploaded to Jenkins
+    File(PathBuf),
+}
+
+pub type BuildParameters = BTreeMap<String, ParameterValue>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefaultParameterValue {
+    pub value: Option<serde_json::Value>,
+}
+
+/// Parameter declared by a job in its `parameterDefinitions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub parameter_type: String,
+    pub description: Option<String>,
+    pub choices: Option<Vec<String>>,
+    pub default_parameter_value: Option<DefaultParameterValue>,
+}
+
+pub fn check_parameters(
+    definitions: &[ParameterDefinition],
+    parameters: &BuildParameters,
+) -> Result<(), Error> {
+    if definitions.is_empty() && !parameters.is_empty() {
+        return Err(Error::InvalidParameter(
+            "job does not take any parameter".to_string(),
+        ));
+    }
+
+    for (name, value) in parameters {
+        let definition = definitions
+            .iter()
+            .find(|definition| &definition.name == name)
+            .ok_or_else(|| {
+                Error::InvalidParameter(format!(
+                    "unknown parameter '{}', expected one of: {}",
+                    name,
+                    definitions
+                        .iter()
+                        .map(|definition| definition.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })?;
+
+        match (definition.parameter_type.as_str(), value) {
+            ("FileParameterDefinition", ParameterValue::File(path)) => {
+                if !path.is_file() {
+                    return Err(Error::InvalidParameter(format!(
+                        "file '{}' for parameter '{}' does not exist",
+                        path.display(),
+                        name
+                    )));
+                }
+            }
+            ("FileParameterDefinition", ParameterValue::Value(_)) => {
+                return Err(Error::InvalidParameter(format!(
+                    "parameter '{}' expects a file",
+                    name
+                )));
+            }
+            (_, ParameterValue::File(_)) => {
+                return Err(Error::InvalidParameter(format!(
+                    "parameter '{}' does not accept a file",
+                    name
+                )));
+            }
+            ("BooleanParameterDefinition", ParameterValue::Value(value)) => {
+                if value != "true" && value != "false" {
+                    return Err(Error::InvalidParameter(format!(
+                        "parameter '{}' expects 'true' or 'false', got '{}'",
+                        name, value
+                    )));
+                }
+            }
+            ("ChoiceParameterDefinition", ParameterValue::Value(value)) => {
+                let choices = definition.choices.as_deref().unwrap_or(&[]);
+                if !choices.contains(value) {
+                    return Err(Error::InvalidParameter(format!(
+                        "parameter '{}' expects one of: {}, got '{}'",
+                        name,
+                        choices.join(", "),
+                        value
+                    )));
+                }
+            }
+            (_, ParameterValue::Value(_)) => (),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(name: &str, parameter_type: &str, choices: &[&str]) -> ParameterDefinition {
+        ParameterDefinition {
+            name: name.to_string(),
+            parameter_type: parameter_type.to_string(),
+            description: None,
+            choices: Some(choices.iter().map(|choice| choice.to_string()).collect()),
+            default_parameter_value: None,
+        }
+    }
+
+    fn definitions() -> Vec<ParameterDefinition> {
+        vec![
+            definition("VERSION", "StringParameterDefinition", &[]),
+            definition("DRY_RUN", "BooleanParameterDefinition", &[]),
+            definition("ENV", "ChoiceParameterDefinition", &["staging", "prod"]),
+            definition("BUNDLE", "FileParameterDefinition", &[]),
+        ]
+    }
+
+    fn check(parameters: &[(&str, ParameterValue)]) -> Result<(), String> {
+        let parameters = parameters
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        check_parameters(&definitions(), &parameters).map_err(|err| err.to_string())
+    }
+
+    fn value(value: &str) -> ParameterValue {
+        ParameterValue::Value(value.to_string())
+    }
+
+    #[test]
+    fn valid_parameters_are_accepted() {
+        let file = std::env::current_exe().unwrap();
+        assert_eq!(check(&[]), Ok(()));
+        assert_eq!(
+            check(&[
+                ("VERSION", value("1.2")),
+                ("DRY_RUN", value("false")),
+                ("ENV", value("prod")),
+                ("BUNDLE", ParameterValue::File(file)),
+            ]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn invalid_parameters_are_explained() {
+        let error = check(&[("VERSIONS", value("1.2"))]).unwrap_err();
+        assert!(
+            error.contains("unknown parameter 'VERSIONS', expected one of: VERSION, DRY_RUN"),
+            "{}",
+            error
+        );
+        let error = check(&[("DRY_RUN", value("yes"))]).unwrap_err();
+        assert!(
+            error.contains("expects 'true' or 'false', got 'yes'"),
+            "{}",
+            error
+        );
+        let error = check(&[("ENV", value("dev"))]).unwrap_err();
+        assert!(
+            error.contains("expects one of: staging, prod, got 'dev'"),
+            "{}",
+            error
+        );
+        let error = check(&[("BUNDLE", value("app.zip"))]).unwrap_err();
+        assert!(
+            error.contains("parameter 'BUNDLE' expects a file"),
+            "{}",
+            error
+        );
+        let error = check(&[("VERSION", ParameterValue::File("app.zip".into()))]).unwrap_err();
+        assert!(error.contains("does not accept a file"), "{}", error);
+        let error =
+            check(&[("BUNDLE", ParameterValue::File("/no/such/bundle.zip".into()))]).unwrap_err();
+        assert!(error.contains("does not exist"), "{}", error);
+    }
+
+    #[test]
+    fn jobs_without_parameters_take_none() {
+        let parameters = vec![("VERSION".to_string(), value("1.2"))]
+            .into_iter()
+            .collect();
+        let error = check_parameters(&[], &parameters).unwrap_err();
+        assert!(error.to_string().contains("does not take any parameter"));
+        assert!(check_parameters(&[], &BuildParameters::new()).is_ok());
+    }
+}
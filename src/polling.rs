@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Delay between two checks of a build still in the queue
+const QUEUED: Duration = Duration::from_secs(2);
+/// Delay between two checks of a build without estimated duration
+const UNKNOWN: Duration = Duration::from_secs(10);
+/// Delay between two checks of a build running longer than estimated
+const OVERDUE: Duration = Duration::from_secs(5);
+/// Longest delay between two checks of a running build
+const LONGEST: Duration = Duration::from_secs(60);
+/// Number of retries after a server error before giving up
+const RETRIES: u32 = 5;
+
+/// When to check a triggered build again
+#[derive(Debug, Clone, Copy)]
+pub enum Polling {
+    /// every X seconds, as given with --polling
+    Fixed(Duration),
+    /// more often as the build gets close to its estimated duration
+    Adaptive,
+}
+
+impl Polling {
+    pub fn new(seconds: Option<u64>) -> Self {
+        match seconds {
+            Some(seconds) => Polling::Fixed(Duration::from_secs(seconds)),
+            None => Polling::Adaptive,
+        }
+    }
+
+    /// Delay before the next check of a build, from whether it started and
+    /// the seconds it has left according to its estimated duration
+    pub fn next(self, started: bool, remaining: Option<i64>) -> Duration {
+        match (self, started, remaining) {
+            (Polling::Fixed(delay), ..) => delay,
+            (Polling::Adaptive, false, _) => QUEUED,
+            (Polling::Adaptive, true, None) => UNKNOWN,
+            (Polling::Adaptive, true, Some(remaining)) if remaining <= 0 => OVERDUE,
+            // a quarter of the time left, to catch the end of the build soon after it happens
+            (Polling::Adaptive, true, Some(remaining)) => {
+                Duration::from_secs((remaining as u64 / 4).clamp(1, LONGEST.as_secs()))
+            }
+        }
+    }
+}
+
+/// Delays between retries after server errors, doubling from a second
+#[derive(Debug, Default)]
+pub struct Backoff {
+    failures: u32,
+}
+
+impl Backoff {
+    /// Delay before retrying after an error, or `None` if the error is not
+    /// worth retrying or there were too many of them
+    pub fn retry(&mut self, err: &jencli::Error) -> Option<Duration> {
+        if !err.is_transient() || self.failures >= RETRIES {
+            return None;
+        }
+        self.failures += 1;
+        Some(Duration::from_secs(1 << (self.failures - 1)))
+    }
+}
+
+/// Sleep for `duration`, returning early with `true` once `interrupted` is set
+pub fn sleep_unless(interrupted: &AtomicBool, duration: Duration) -> bool {
+    let end = Instant::now() + duration;
+    while !interrupted.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= end {
+            return false;
+        }
+        thread::sleep((end - now).min(Duration::from_millis(100)));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error(status: u16) -> jencli::Error {
+        jencli::Error::Server {
+            status,
+            url: "http://jenkins/queue/item/1/api/json".to_string(),
+        }
+    }
+
+    #[test]
+    fn fixed_polling_ignores_the_build() {
+        let polling = Polling::new(Some(3));
+        assert_eq!(polling.next(false, None), Duration::from_secs(3));
+        assert_eq!(polling.next(true, Some(600)), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn adaptive_polling_follows_the_estimated_duration() {
+        let polling = Polling::new(None);
+        assert_eq!(polling.next(false, Some(600)), QUEUED);
+        assert_eq!(polling.next(true, None), UNKNOWN);
+        assert_eq!(polling.next(true, Some(0)), OVERDUE);
+        assert_eq!(polling.next(true, Some(-30)), OVERDUE);
+        assert_eq!(polling.next(true, Some(2)), Duration::from_secs(1));
+        assert_eq!(polling.next(true, Some(40)), Duration::from_secs(10));
+        assert_eq!(polling.next(true, Some(3600)), LONGEST);
+    }
+
+    #[test]
+    fn backoff_doubles_until_it_gives_up() {
+        let mut backoff = Backoff::default();
+        let delays = (0..RETRIES + 1)
+            .map(|_| backoff.retry(&server_error(503)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_secs(1)),
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(4)),
+                Some(Duration::from_secs(8)),
+                Some(Duration::from_secs(16)),
+                None,
+            ]
+        );
+
+        assert_eq!(Backoff::default().retry(&server_error(404)), None);
+    }
+
+    #[test]
+    fn sleep_returns_early_once_interrupted() {
+        let interrupted = AtomicBool::new(false);
+        assert!(!sleep_unless(&interrupted, Duration::from_millis(10)));
+        interrupted.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        assert!(sleep_unless(&interrupted, Duration::from_secs(60)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
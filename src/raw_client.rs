@@ -0,0 +1,90 @@
+use jenkins_api::client::AdvancedQuery;
+use log::debug;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::Error;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Crumb {
+    crumb: String,
+    crumb_request_field: String,
+}
+
+/// HTTP client for Jenkins, sharing its connection pool with its clones
+#[derive(Clone)]
+pub(crate) struct RawClient {
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    client: Client,
+}
+
+impl RawClient {
+    pub(crate) fn new(
+        url: &str,
+        user: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self, Error> {
+        Ok(RawClient {
+            url: url.trim_end_matches('/').to_string(),
+            user,
+            password,
+            client: Client::builder().build()?,
+        })
+    }
+
+    fn with_auth(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.user {
+            Some(ref user) => request.basic_auth(user.clone(), self.password.clone()),
+            None => request,
+        }
+    }
+
+    pub(crate) fn get(&self, path: &str) -> RequestBuilder {
+        self.with_auth(self.client.get(&format!("{}{}", self.url, path)))
+    }
+
+    pub(crate) fn head(&self, path: &str) -> RequestBuilder {
+        self.with_auth(self.client.head(&format!("{}{}", self.url, path)))
+    }
+
+    /// Get the JSON API of the object at `path`, with `depth` or `tree` to select the fields
+    pub(crate) fn get_object<T, Q>(&self, path: &str, query: Q) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        Q: Into<Option<AdvancedQuery>>,
+    {
+        let request = self.get(&format!("{}/api/json", path));
+        let request = match query.into() {
+            Some(AdvancedQuery::Depth(depth)) => request.query(&[("depth", depth.to_string())]),
+            Some(AdvancedQuery::Tree(tree)) => request.query(&[("tree", tree.to_string())]),
+            None => request,
+        };
+        Ok(RawClient::send(request)?.json()?)
+    }
+
+    pub(crate) fn post(&self, path: &str) -> Result<RequestBuilder, Error> {
+        let request = self.with_auth(self.client.post(&format!("{}{}", self.url, path)));
+        Ok(match self.crumb()? {
+            Some(crumb) => request.header(crumb.crumb_request_field.as_str(), crumb.crumb.as_str()),
+            None => request,
+        })
+    }
+
+    fn crumb(&self) -> Result<Option<Crumb>, Error> {
+        let response = self.get("/crumbIssuer/api/json").send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json()?))
+    }
+
+    pub(crate) fn send(request: RequestBuilder) -> Result<Response, Error> {
+        let response = request.send()?;
+        debug!("{} {}", response.status(), response.url());
+        Ok(response.error_for_status()?)
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// `_class` of the builds of Pipeline jobs
+pub const PIPELINE_RUN_CLASS: &str = "org.jenkinsci.plugins.workflow.job.WorkflowRun";
+
+/// A run of a Pipeline job, as described by the Workflow REST API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineRun {
+    pub id: String,
+    pub name: String,
+    /// SUCCESS, FAILED, UNSTABLE, ABORTED, IN_PROGRESS, PAUSED_PENDING_INPUT or NOT_EXECUTED
+    pub status: String,
+    #[serde(default)]
+    pub start_time_millis: i64,
+    #[serde(default)]
+    pub duration_millis: i64,
+    #[serde(default)]
+    pub pause_duration_millis: i64,
+    #[serde(default)]
+    pub queue_duration_millis: i64,
+    #[serde(default)]
+    pub stages: Vec<Stage>,
+}
+
+/// A stage of a Pipeline run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stage {
+    pub id: String,
+    pub name: String,
+    /// node the stage ran on, empty for the master
+    #[serde(default)]
+    pub exec_node: String,
+    pub status: String,
+    #[serde(default)]
+    pub start_time_millis: i64,
+    #[serde(default)]
+    pub duration_millis: i64,
+    /// time spent waiting for an input
+    #[serde(default)]
+    pub pause_duration_millis: i64,
+}
+
+impl PipelineRun {
+    /// Stage running or waiting for an input, or the last stage once the run is finished
+    pub fn current_stage(&self) -> Option<&Stage> {
+        self.stages
+            .iter()
+            .rev()
+            .find(|stage| stage.status == "IN_PROGRESS" || stage.status == "PAUSED_PENDING_INPUT")
+            .or_else(|| self.stages.last())
+    }
+}
+
+/// An `input` step of a Pipeline run, waiting for someone to proceed or abort
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputAction {
+    pub id: String,
+    pub message: String,
+    /// label of the button to proceed, like `Proceed`
+    pub proceed_text: String,
+    pub inputs: Vec<InputParameter>,
+    /// how the input was answered, when it was
+    pub answer: Option<InputAnswer>,
+}
+
+/// A parameter asked by an `input` step
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputParameter {
+    pub name: String,
+    /// type of the parameter, like `StringParameterDefinition`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub description: Option<String>,
+    pub default_value: Option<Value>,
+    /// possible values of a choice parameter
+    pub choices: Vec<String>,
+}
+
+/// Answer given to an `input` step
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputAnswer {
+    pub proceeded: bool,
+    pub parameters: BTreeMap<String, String>,
+    /// user who answered
+    pub user: String,
+    pub timestamp: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PendingInputAction {
+    id: String,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    proceed_text: String,
+    #[serde(default)]
+    inputs: Vec<PendingInput>,
+}
+
+#[derive(Deserialize)]
+struct PendingInput {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    description: Option<String>,
+    #[serde(default)]
+    definition: Value,
+}
+
+impl From<PendingInputAction> for InputAction {
+    fn from(action: PendingInputAction) -> Self {
+        let PendingInputAction {
+            id,
+            message,
+            proceed_text,
+            inputs,
+        } = action;
+        InputAction {
+            id,
+            message,
+            proceed_text,
+            inputs: inputs
+                .into_iter()
+                .map(|input| InputParameter {
+                    default_value: input
+                        .definition
+                        .get("defaultParameterValue")
+                        .and_then(|value| value.get("value"))
+                        .cloned(),
+                    choices: input
+                        .definition
+                        .get("choices")
+                        .and_then(Value::as_array)
+                        .map(|choices| {
+                            choices
+                                .iter()
+                                .filter_map(Value::as_str)
+                                .map(ToString::to_string)
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    name: input.name,
+                    kind: input.kind,
+                    description: input
+                        .description
+                        .filter(|description| !description.is_empty()),
+                })
+                .collect(),
+            answer: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StageDescription {
+    #[serde(default)]
+    pub(crate) stage_flow_nodes: Vec<FlowNode>,
+}
+
+/// A step of a stage
+#[derive(Deserialize)]
+pub(crate) struct FlowNode {
+    pub(crate) id: String,
+    #[serde(rename = "_links", default)]
+    links: FlowNodeLinks,
+}
+
+#[derive(Deserialize, Default)]
+struct FlowNodeLinks {
+    log: Option<serde_json::Value>,
+}
+
+impl FlowNode {
+    pub(crate) fn has_log(&self) -> bool {
+        self.links.log.is_some()
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FlowNodeLog {
+    #[serde(default)]
+    pub(crate) text: String,
+}
+
+lazy_static! {
+    static ref TAG: Regex = Regex::new("<[^>]*>").expect("invalid regex");
+}
+
+/// Text of a log sent as HTML by the Workflow REST API
+pub(crate) fn html_to_text(html: &str) -> String {
+    TAG.replace_all(html, "")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Results of the tests of a build, from its `testReport`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestReport {
+    pub fail_count: u32,
+    pub pass_count: u32,
+    pub skip_count: u32,
+    #[serde(default)]
+    pub suites: Vec<TestSuite>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestSuite {
+    pub name: String,
+    #[serde(default)]
+    pub cases: Vec<TestCase>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestCase {
+    pub class_name: String,
+    pub name: String,
+    /// one of PASSED, FIXED, SKIPPED, FAILED or REGRESSION
+    pub status: String,
+    pub duration: f64,
+    pub error_details: Option<String>,
+    pub error_stack_trace: Option<String>,
+}
+
+impl TestCase {
+    pub fn full_name(&self) -> String {
+        format!("{}.{}", self.class_name, self.name)
+    }
+
+    pub fn is_failing(&self) -> bool {
+        self.status == "FAILED" || self.status == "REGRESSION"
+    }
+}
+
+impl TestReport {
+    pub fn cases(&self) -> impl Iterator<Item = &TestCase> {
+        self.suites.iter().flat_map(|suite| suite.cases.iter())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &TestCase> {
+        self.cases().filter(|case| case.is_failing())
+    }
+}
+
+/// Counts of a test report with its failing cases
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestSummary {
+    pub fail_count: u32,
+    pub pass_count: u32,
+    pub skip_count: u32,
+    pub failures: Vec<TestCase>,
+}
+
+impl TestSummary {
+    /// Summarize a report, keeping the stack traces of failures only if `stack_traces` is set
+    pub fn new(report: &TestReport, stack_traces: bool) -> Self {
+        TestSummary {
+            fail_count: report.fail_count,
+            pass_count: report.pass_count,
+            skip_count: report.skip_count,
+            failures: report
+                .failures()
+                .cloned()
+                .map(|mut case| {
+                    if !stack_traces {
+                        case.error_stack_trace = None;
+                    }
+                    case
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Failing tests compared between a build and a previous one
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestDiff {
+    /// failing now, but not in the previous build
+    pub new_failures: Vec<TestCase>,
+    /// failing in the previous build, but not anymore
+    pub fixed: Vec<TestCase>,
+    /// failing in both builds
+    pub still_failing: Vec<TestCase>,
+}
+
+impl TestDiff {
+    pub fn new(previous: &TestReport, current: &TestReport) -> Self {
+        let previous_failures = previous
+            .failures()
+            .map(|case| (case.full_name(), case))
+            .collect::<BTreeMap<_, _>>();
+        let current_failures = current
+            .failures()
+            .map(|case| (case.full_name(), case))
+            .collect::<BTreeMap<_, _>>();
+
+        TestDiff {
+            new_failures: current_failures
+                .iter()
+                .filter(|(name, _)| !previous_failures.contains_key(*name))
+                .map(|(_, case)| (*case).clone())
+                .collect(),
+            fixed: previous_failures
+                .iter()
+                .filter(|(name, _)| !current_failures.contains_key(*name))
+                .map(|(_, case)| (*case).clone())
+                .collect(),
+            still_failing: current_failures
+                .iter()
+                .filter(|(name, _)| previous_failures.contains_key(*name))
+                .map(|(_, case)| (*case).clone())
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use jencli::mock::{Lifecycle, MockJenkins};
+use jenkins_api::build::BuildStatus;
+
+#[test]
+fn search_jobs_in_folders() {
+    let mock = MockJenkins::start();
+    mock.add_job("tools", Lifecycle::default());
+    mock.add_job("team/app", Lifecycle::default());
+    mock.add_job("team/lib", Lifecycle::default());
+
+    let names = jencli::search_job(&mock.jenkins(), "^team/", 2)
+        .unwrap()
+        .map(|job| job.name)
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, vec!["team/app", "team/lib"]);
+}
+
+#[test]
+fn triggered_build_goes_through_queue() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "app",
+        Lifecycle {
+            queued_polls: 1,
+            running_polls: 1,
+            result: BuildStatus::Unstable,
+        },
+    );
+    let jenkins = mock.jenkins();
+
+    let item = jencli::trigger_job(&jenkins, "app", &Default::default()).unwrap();
+    let queued = jencli::get_queue_item(&jenkins, &item).unwrap();
+    assert!(queued.executable.is_none());
+    assert_eq!(jencli::get_queue(&jenkins).unwrap().count(), 1);
+
+    let started = jencli::get_queue_item(&jenkins, &item).unwrap();
+    let number = started.executable.unwrap().number;
+    assert_eq!(jencli::get_queue(&jenkins).unwrap().count(), 0);
+
+    let running = jencli::get_build(&jenkins, "app", Some(number)).unwrap();
+    assert!(running.building);
+    let finished = jencli::get_build(&jenkins, "app", Some(number)).unwrap();
+    assert_eq!(finished.result, Some(BuildStatus::Unstable));
+    assert!(jencli::get_console(&jenkins, "app", Some(number))
+        .unwrap()
+        .ends_with("Finished: UNSTABLE\n"));
+}
+
+#[test]
+fn executors_show_running_builds() {
+    let mock = MockJenkins::start();
+    mock.add_node("agent", 1);
+    mock.add_job("app", Lifecycle::default());
+    mock.add_job("lib", Lifecycle::default());
+    mock.add_build("app", Some(BuildStatus::Success));
+    mock.add_build("app", None);
+    mock.add_build("lib", None);
+    mock.add_build("lib", None);
+
+    let executors = jencli::get_executors(&mock.jenkins())
+        .unwrap()
+        .map(|executor| (executor.node, executor.build.unwrap().full_display_name))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        executors,
+        vec![
+            ("master".to_string(), Some("app #2".to_string())),
+            ("master".to_string(), Some("lib #1".to_string())),
+            ("agent".to_string(), Some("lib #2".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn tick_advances_queue_and_builds() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "app",
+        Lifecycle {
+            queued_polls: 0,
+            running_polls: 0,
+            result: BuildStatus::Failure,
+        },
+    );
+    let jenkins = mock.jenkins();
+    mock.enqueue("app");
+
+    mock.tick();
+    assert_eq!(jencli::get_executors(&jenkins).unwrap().count(), 1);
+    mock.tick();
+    assert_eq!(jencli::get_executors(&jenkins).unwrap().count(), 0);
+
+    let build = jencli::list_builds(&jenkins, "app", 10)
+        .unwrap()
+        .next()
+        .unwrap();
+    assert_eq!(build.result, Some(BuildStatus::Failure));
+}
+
+#[test]
+fn stop_running_build_and_cancel_queued_item() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    let number = mock.add_build("app", None);
+    let id = mock.enqueue("app");
+    let jenkins = mock.jenkins();
+
+    let stopped = jencli::stop_build(
+        &jenkins,
+        "app",
+        Some(number),
+        jencli::StopSignal::Stop,
+        Duration::from_secs(0),
+    )
+    .unwrap();
+    assert_eq!(stopped.result, Some(BuildStatus::Aborted));
+
+    jencli::cancel_queue_item(&jenkins, id as i32).unwrap();
+    assert_eq!(jencli::get_queue(&jenkins).unwrap().count(), 0);
+}
+
+#[test]
+fn stop_matching_builds_goes_on_after_a_failure() {
+    let mock = MockJenkins::start();
+    mock.add_job("deploy-db", Lifecycle::default());
+    mock.add_job("deploy-web", Lifecycle::default());
+    mock.add_build("deploy-db", None);
+    mock.add_build("deploy-web", None);
+    mock.fail_requests_to("/job/deploy-db/1/stop");
+    let jenkins = mock.jenkins();
+
+    let results = jencli::stop_matching_builds(
+        &jenkins,
+        "^deploy-",
+        jencli::StopSignal::Stop,
+        Duration::from_secs(0),
+    )
+    .unwrap()
+    .collect::<Vec<_>>();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+
+    let build = jencli::get_build(&jenkins, "deploy-web", Some(1)).unwrap();
+    assert_eq!(build.result, Some(BuildStatus::Aborted));
+}
+
+#[test]
+fn download_artifacts_with_checksum() {
+    // served under a context path, as artifact URLs linked by Jenkins include it
+    let mock = MockJenkins::start_under("/jenkins");
+    mock.add_job("app", Lifecycle::default());
+    let number = mock.add_build("app", Some(BuildStatus::Success));
+    mock.add_artifact("app", number, "target/app.jar", b"jar content");
+    let jenkins = mock.jenkins();
+    let dest = std::env::temp_dir().join(format!("jencli-artifacts-{}", std::process::id()));
+
+    let artifacts = jencli::list_artifacts(&jenkins, "app", None, true)
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(artifacts.len(), 1);
+    assert_eq!(artifacts[0].size, Some(11));
+
+    // without sizes, no HEAD request is sent
+    let requests = mock.requests().len();
+    let artifacts = jencli::list_artifacts(&jenkins, "app", None, false)
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(artifacts[0].size, None);
+    assert_eq!(mock.requests().len(), requests + 1);
+
+    let downloaded = jencli::download_artifact(&jenkins, &artifacts[0], &dest).unwrap();
+    assert!(downloaded.verified);
+    assert_eq!(downloaded.size, 11);
+    assert_eq!(
+        std::fs::read(dest.join("target").join("app.jar")).unwrap(),
+        b"jar content"
+    );
+    std::fs::remove_dir_all(dest).unwrap();
+}
@@ -0,0 +1,1692 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use jencli::mock::{Lifecycle, MockJenkins};
+use jenkins_api::build::BuildStatus;
+
+/// Empty home directory for jencli, removed when dropped
+struct TempHome(PathBuf);
+
+impl TempHome {
+    fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "jencli-home-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        TempHome(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempHome {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// jencli talking to `mock`, with `home` as home, config and cache
+/// directories and current directory, so that the configuration, tokens and
+/// caches of the user running the tests are left out
+fn command(mock: &MockJenkins, home: &TempHome) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_jencli"));
+    command
+        .env_remove("JENKINS_USER")
+        .env_remove("JENKINS_PASSWORD")
+        .env_remove("JENKINS_PROFILE")
+        .env_remove("JENKINS_TOKEN_FILE")
+        .env_remove("JENKINS_PASSWORD_COMMAND")
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join(".config"))
+        .env("XDG_CACHE_HOME", home.path().join(".cache"))
+        .current_dir(home.path())
+        .args(["--url", mock.url()]);
+    command
+}
+
+fn jencli_in(home: &TempHome, mock: &MockJenkins, args: &[&str]) -> Output {
+    command(mock, home)
+        .args(args)
+        .output()
+        .expect("could not run jencli")
+}
+
+fn jencli(mock: &MockJenkins, args: &[&str]) -> Output {
+    jencli_in(&TempHome::new(), mock, args)
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn trigger_waits_for_result_and_exits_with_its_code() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "app",
+        Lifecycle {
+            queued_polls: 1,
+            running_polls: 2,
+            result: BuildStatus::Failure,
+        },
+    );
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "app",
+            "--wait-finish",
+            "--polling",
+            "0",
+            "-t",
+            "{{#if build}}{{ build.displayName }} {{#if build.building}}running{{else}}{{ build.result }}{{/if}}{{else}}queued{{/if}}",
+        ],
+    );
+
+    assert_eq!(
+        stdout(&output),
+        "queued\n#1 running\n#1 running\n#1 FAILURE\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn tool_errors_exit_apart_from_build_results() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    mock.add_build("app", Some(BuildStatus::Failure));
+
+    let output = jencli(&mock, &["build", "app", "-t", "{{ result }}"]);
+    assert_eq!(stdout(&output), "FAILURE\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = jencli(&mock, &["build", "missing"]);
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error: "), "{}", stderr);
+
+    for args in &[
+        &["build"][..],
+        &["trigger", "app", "--polling", "soon"],
+        &["unknown"],
+    ] {
+        let output = jencli(&mock, args);
+        assert_eq!(output.status.code(), Some(10), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("error: "), "{}", stderr);
+    }
+
+    let output = jencli(&mock, &["--help"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("About Exit Codes"));
+}
+
+#[test]
+fn running_lists_executors_and_queue() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    mock.add_build("app", None);
+    mock.enqueue("app");
+
+    let output = jencli(&mock, &["running"]);
+
+    let stdout = stdout(&output);
+    assert!(output.status.success());
+    assert!(stdout.contains("app #1"), "{}", stdout);
+    assert!(
+        stdout.contains("app Waiting for next available executor"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn pipeline_builds_run_on_one_off_executors() {
+    let mock = MockJenkins::start();
+    mock.add_job("pipeline", Lifecycle::default());
+    mock.set_stages("pipeline", &["Build", "Test"]);
+    mock.add_build("pipeline", None);
+    mock.add_job("app", Lifecycle::default());
+    mock.add_build("app", None);
+
+    let output = jencli(&mock, &["running", "--no-queued"]);
+    let text = stdout(&output);
+    assert!(text.contains("pipeline #1"), "{}", text);
+    assert!(text.contains("app #1"), "{}", text);
+
+    let output = jencli(
+        &mock,
+        &[
+            "stop",
+            "--all-matching",
+            "^pipe",
+            "-t",
+            "{{ fullDisplayName }}",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "pipeline #1\n");
+
+    let output = jencli(&mock, &["build", "pipeline", "-t", "{{ result }}"]);
+    assert_eq!(stdout(&output), "ABORTED\n");
+}
+
+#[test]
+fn stop_all_matching_renders_builds_and_reports_failures() {
+    let mock = MockJenkins::start();
+    mock.add_node("agent", 2);
+    for name in &["app-api", "app-web", "tools"] {
+        mock.add_job(name, Lifecycle::default());
+        mock.add_build(name, None);
+    }
+    mock.fail_requests_to("/job/app-web/1/stop");
+
+    let output = jencli(&mock, &["-o", "json", "stop", "--all-matching", "^app-"]);
+
+    let builds = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(builds.len(), 1);
+    assert_eq!(builds[0]["fullDisplayName"], "app-api #1");
+    assert_eq!(builds[0]["result"], "ABORTED");
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: could not stop 1 of the matching builds"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn build_renders_template_and_table() {
+    let mock = MockJenkins::start();
+    mock.add_job("team/app", Lifecycle::default());
+    mock.add_build("team/app", Some(BuildStatus::Success));
+
+    let output = jencli(
+        &mock,
+        &[
+            "build",
+            "team/app",
+            "-t",
+            "{{ fullDisplayName }} {{ result }}",
+        ],
+    );
+    assert_eq!(stdout(&output), "team » app #1 SUCCESS\n");
+
+    let output = jencli(
+        &mock,
+        &[
+            "-o",
+            "table",
+            "--columns",
+            "number,result",
+            "build",
+            "team/app",
+        ],
+    );
+    assert_eq!(stdout(&output), "NUMBER  RESULT\n1       SUCCESS\n");
+    assert!(output.status.success());
+
+    // a build without result yet does not pass as a success
+    mock.add_build("team/app", None);
+    let output = jencli(&mock, &["build", "team/app", "-t", "{{ number }}"]);
+    assert_eq!(stdout(&output), "2\n");
+    assert_eq!(output.status.code(), Some(6));
+}
+
+#[test]
+fn structured_output_can_be_parsed_back() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    mock.add_build("app", Some(BuildStatus::Success));
+    mock.add_build("app", Some(BuildStatus::Failure));
+
+    let fields = |builds: &[serde_json::Value]| {
+        builds
+            .iter()
+            .map(|build| {
+                (
+                    build["number"].as_u64().unwrap(),
+                    build["result"].as_str().unwrap().to_string(),
+                    build["url"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let expected = vec![
+        (
+            2,
+            "FAILURE".to_string(),
+            format!("{}/job/app/2/", mock.url()),
+        ),
+        (
+            1,
+            "SUCCESS".to_string(),
+            format!("{}/job/app/1/", mock.url()),
+        ),
+    ];
+
+    // one JSON document per line
+    let output = jencli(&mock, &["-o", "json", "builds", "app"]);
+    assert!(output.status.success());
+    let builds = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect::<Vec<serde_json::Value>>();
+    assert_eq!(fields(&builds), expected);
+
+    let output = jencli(&mock, &["-o", "json-array", "builds", "app"]);
+    let builds: Vec<serde_json::Value> = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(fields(&builds), expected);
+
+    let output = jencli(&mock, &["-o", "yaml", "builds", "app"]);
+    assert!(output.status.success());
+    let builds: Vec<serde_json::Value> = serde_yaml::from_str(&stdout(&output)).unwrap();
+    assert_eq!(fields(&builds), expected);
+
+    // a single item is still a list
+    let output = jencli(&mock, &["-o", "yaml", "build", "app", "1"]);
+    let builds: Vec<serde_json::Value> = serde_yaml::from_str(&stdout(&output)).unwrap();
+    assert_eq!(fields(&builds), expected[1..]);
+
+    let output = jencli(
+        &mock,
+        &["-o", "csv", "--columns", "number,result", "builds", "app"],
+    );
+    assert_eq!(stdout(&output), "number,result\n2,FAILURE\n1,SUCCESS\n");
+}
+
+#[test]
+fn nodes_are_put_offline_and_back_online() {
+    let mock = MockJenkins::start();
+    mock.add_node("agent-1", 2);
+    mock.add_label("agent-1", "linux");
+    mock.add_label("agent-1", "docker");
+    mock.set_offline("agent-1", "disk full");
+    mock.add_job("app", Lifecycle::default());
+    mock.add_build("app", None);
+
+    let output = jencli(&mock, &["nodes"]);
+    assert_eq!(
+        stdout(&output),
+        "master\tonline\t1/2 busy\t\nagent-1\toffline (disk full)\t0/2 busy\tlinux docker \n"
+    );
+
+    let output = jencli(
+        &mock,
+        &[
+            "nodes",
+            "^master$",
+            "-t",
+            "{{ architecture }} {{ diskSpace }}",
+        ],
+    );
+    assert_eq!(stdout(&output), "Linux (amd64) 10737418240\n");
+
+    let output = jencli(&mock, &["node", "online", "agent-1"]);
+    assert_eq!(stdout(&output), "agent-1\tonline\n");
+
+    let output = jencli(
+        &mock,
+        &["node", "offline", "master", "--reason", "upgrade to 2.303"],
+    );
+    assert_eq!(stdout(&output), "master\toffline (upgrade to 2.303)\n");
+
+    let output = jencli(&mock, &["node", "offline", "unknown"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn config_is_compared_and_pushed() {
+    let mock = MockJenkins::start();
+    mock.add_job("team/app", Lifecycle::default());
+    let file = std::env::temp_dir().join(format!("jencli-config-{}.xml", std::process::id()));
+    std::fs::write(
+        &file,
+        "<project><description>built by make</description><keepDependencies>false</keepDependencies>\
+         <builders><hudson.tasks.Shell><command>make test</command></hudson.tasks.Shell></builders></project>",
+    )
+    .unwrap();
+    let file_path = file.to_str().unwrap();
+
+    let output = jencli(&mock, &["config", "get", "team/app"]);
+    assert!(stdout(&output).contains("<command>make</command>"));
+
+    let output = jencli(&mock, &["config", "diff", "team/app", file_path]);
+    assert_eq!(
+        stdout(&output),
+        "+ /project/description: built by make\n\
+         - /project/builders/hudson.tasks.Shell/command: make\n\
+         + /project/builders/hudson.tasks.Shell/command: make test\n"
+    );
+
+    let output = jencli(
+        &mock,
+        &["config", "push", "team/app", file_path, "--dry-run"],
+    );
+    assert!(stdout(&output).starts_with("would update team/app (2 changes)\n"));
+    assert!(mock
+        .config("team/app")
+        .unwrap()
+        .contains("<command>make</command>"));
+
+    let output = jencli(&mock, &["config", "push", "team/app", file_path]);
+    assert_eq!(stdout(&output), "updated team/app (2 changes)\n");
+    assert!(mock.config("team/app").unwrap().contains("make test"));
+
+    let output = jencli(&mock, &["config", "push", "team/lib", file_path]);
+    assert_eq!(stdout(&output), "created team/lib (3 changes)\n");
+    assert!(mock.config("team/lib").is_some());
+
+    std::fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn jobs_are_disabled_copied_renamed_and_deleted() {
+    let mock = MockJenkins::start();
+    mock.add_job("legacy-app", Lifecycle::default());
+    mock.add_job("legacy-lib", Lifecycle::default());
+    mock.add_job("tools", Lifecycle::default());
+
+    let output = jencli(
+        &mock,
+        &[
+            "disable",
+            "--all-matching",
+            "^legacy-",
+            "--yes",
+            "-t",
+            "{{ fullName }} {{ color }}",
+        ],
+    );
+    assert_eq!(
+        stdout(&output),
+        "legacy-app disabled\nlegacy-lib disabled\n"
+    );
+
+    let output = jencli(
+        &mock,
+        &["enable", "legacy-lib", "-t", "{{ fullName }} {{ color }}"],
+    );
+    assert_eq!(stdout(&output), "legacy-lib notbuilt\n");
+
+    let output = jencli(&mock, &["copy", "tools", "team/tools"]);
+    assert_eq!(stdout(&output), "copied to team/tools\n");
+
+    let output = jencli(&mock, &["rename", "team/tools", "helpers"]);
+    assert_eq!(stdout(&output), "renamed to team/helpers\n");
+
+    // deleting without --yes reads the confirmation from stdin, closed here
+    let output = jencli(&mock, &["delete", "legacy-app"]);
+    assert!(!output.status.success());
+    assert!(mock.config("legacy-app").is_some());
+
+    let output = jencli(&mock, &["delete", "legacy-app", "--yes"]);
+    assert_eq!(stdout(&output), "deleted legacy-app\n");
+    assert!(mock.config("legacy-app").is_none());
+}
+
+#[test]
+fn stages_of_pipeline_build_and_their_log() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "app",
+        Lifecycle {
+            queued_polls: 1,
+            running_polls: 2,
+            result: BuildStatus::Failure,
+        },
+    );
+    mock.set_stages("app", &["build", "test", "deploy"]);
+    mock.add_build("app", Some(BuildStatus::Failure));
+
+    let output = jencli(&mock, &["stages", "app", "-t", "{{ name }} {{ status }}"]);
+    assert_eq!(
+        stdout(&output),
+        "build SUCCESS\ntest SUCCESS\ndeploy FAILED\n"
+    );
+
+    let output = jencli(&mock, &["stages", "app", "1", "--log", "test"]);
+    assert_eq!(stdout(&output), "+ make test\n<test> done\n");
+
+    let output = jencli(&mock, &["stages", "app", "--log", "lint"]);
+    assert!(!output.status.success());
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "app",
+            "--wait-finish",
+            "--polling",
+            "0",
+            "-t",
+            "{{#if build.stage}}{{ build.stage.name }} {{ build.stage.status }}{{/if}}",
+        ],
+    );
+    assert_eq!(
+        stdout(&output),
+        "\nbuild IN_PROGRESS\ntest IN_PROGRESS\ndeploy FAILED\n"
+    );
+}
+
+/// Test report with one case per (class, name, status), counted by status
+fn test_report(cases: &[(&str, &str, &str)]) -> serde_json::Value {
+    let count = |statuses: &[&str]| {
+        cases
+            .iter()
+            .filter(|(_, _, status)| statuses.contains(status))
+            .count()
+    };
+    serde_json::json!({
+        "passCount": count(&["PASSED", "FIXED"]),
+        "failCount": count(&["FAILED", "REGRESSION"]),
+        "skipCount": count(&["SKIPPED"]),
+        "suites": [{
+            "name": "suite",
+            "cases": cases.iter().map(|(class_name, name, status)| serde_json::json!({
+                "className": class_name,
+                "name": name,
+                "status": status,
+                "duration": 0.5,
+                "errorDetails": if status.starts_with('F') || *status == "REGRESSION" {
+                    Some(format!("{} broke", name))
+                } else {
+                    None
+                },
+                "errorStackTrace": "at Suite.run",
+            })).collect::<Vec<_>>(),
+        }],
+    })
+}
+
+#[test]
+fn tests_summarize_failures_and_compare_builds() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    mock.add_build("app", Some(BuildStatus::Unstable));
+    mock.add_build("app", Some(BuildStatus::Unstable));
+    mock.add_build("app", Some(BuildStatus::Success));
+    mock.set_test_report(
+        "app",
+        1,
+        test_report(&[
+            ("app.Api", "login", "FAILED"),
+            ("app.Api", "logout", "FAILED"),
+            ("app.Db", "query", "PASSED"),
+        ]),
+    );
+    mock.set_test_report(
+        "app",
+        2,
+        test_report(&[
+            ("app.Api", "login", "FAILED"),
+            ("app.Api", "logout", "FIXED"),
+            ("app.Db", "query", "REGRESSION"),
+            ("app.Db", "migrate", "SKIPPED"),
+        ]),
+    );
+
+    let output = jencli(&mock, &["tests", "app", "2"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "1 passed, 2 failed, 1 skipped\n\
+         \u{1b}[31mFAILED\u{1b}[0m app.Api.login\n    login broke\n\
+         \u{1b}[1;31mREGRESSION\u{1b}[0m app.Db.query\n    query broke\n"
+    );
+
+    let output = jencli(&mock, &["tests", "app", "1", "--stack-traces"]);
+    assert_eq!(
+        stdout(&output),
+        "1 passed, 2 failed, 0 skipped\n\
+         \u{1b}[31mFAILED\u{1b}[0m app.Api.login\n    login broke\nat Suite.run\n\
+         \u{1b}[31mFAILED\u{1b}[0m app.Api.logout\n    logout broke\nat Suite.run\n"
+    );
+
+    let output = jencli(&mock, &["tests", "app", "2", "--diff", "1"]);
+    assert_eq!(
+        stdout(&output),
+        "1 new failures, 1 fixed, 1 still failing\n\
+         new\tapp.Db.query\n\
+         fixed\tapp.Api.logout\n\
+         still\tapp.Api.login\n"
+    );
+
+    // the last build has no test report
+    let output = jencli(&mock, &["tests", "app"]);
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn log_is_printed_and_followed_until_the_build_finishes() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "app",
+        Lifecycle {
+            queued_polls: 0,
+            running_polls: 3,
+            result: BuildStatus::Unstable,
+        },
+    );
+    mock.add_build("app", Some(BuildStatus::Success));
+    mock.add_build("app", None);
+
+    let output = jencli(&mock, &["log", "app", "1"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Started\nFinished: SUCCESS\n");
+
+    let output = jencli(&mock, &["log", "app", "--follow", "--polling", "0"]);
+    assert!(output.status.success());
+    let followed = stdout(&output);
+    assert!(followed.starts_with("Started\nstep 1\n"));
+    assert!(followed.ends_with("Finished: UNSTABLE\n"));
+    assert_eq!(followed, stdout(&jencli(&mock, &["log", "app", "2"])));
+
+    // each poll asks for what comes after the output already printed
+    let starts = mock
+        .requests()
+        .into_iter()
+        .filter(|request| request.starts_with("GET /job/app/2/logText/progressiveText"))
+        .map(|request| {
+            request
+                .rsplit('=')
+                .next()
+                .unwrap()
+                .parse::<usize>()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert!(starts.len() > 2);
+    assert_eq!(starts[0], 0);
+    assert!(starts.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(starts.iter().all(|start| *start < followed.len()));
+
+    // a finished build is printed in one poll
+    let output = jencli(&mock, &["log", "app", "1", "--follow"]);
+    assert_eq!(stdout(&output), "Started\nFinished: SUCCESS\n");
+}
+
+#[test]
+fn completion_lists_job_and_view_names() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    mock.add_job("team/api", Lifecycle::default());
+    mock.add_job("tools", Lifecycle::default());
+    mock.add_view("team", &["team/api"]);
+    let home = TempHome::new();
+
+    let complete =
+        |args: &[&str]| stdout(&jencli_in(&home, &mock, &[&["__complete"], args].concat()));
+    // folders are completed too, to go on with the name of a job inside
+    assert_eq!(complete(&["jobs"]), "app\nteam\nteam/api\ntools\n");
+    assert_eq!(complete(&["jobs", "te"]), "team\nteam/api\n");
+    assert_eq!(complete(&["views"]), "all\nteam\n");
+
+    // names are taken from the cache until it expires
+    mock.add_job("team/web", Lifecycle::default());
+    assert_eq!(complete(&["jobs", "team/"]), "team/api\n");
+    assert_eq!(
+        complete(&["jobs", "team/", "--ttl", "0"]),
+        "team/api\nteam/web\n"
+    );
+
+    // credentials are only needed when the cache can not be used
+    let missing = home.path().join("no-token");
+    let missing = missing.to_str().unwrap();
+    let output = jencli_in(
+        &home,
+        &mock,
+        &["--token-file", missing, "__complete", "jobs", "team/"],
+    );
+    assert_eq!(stdout(&output), "team/api\nteam/web\n");
+    let output = jencli_in(
+        &home,
+        &mock,
+        &["--token-file", missing, "__complete", "jobs", "--ttl", "0"],
+    );
+    assert!(!output.status.success());
+
+    let output = jencli(&mock, &["completions", "bash"]);
+    assert!(stdout(&output).contains("jencli __complete jobs"));
+}
+
+#[test]
+fn inputs_are_listed_proceeded_and_aborted() {
+    let mock = MockJenkins::start();
+    mock.add_job("deploy", Lifecycle::default());
+    mock.set_stages("deploy", &["build", "approve"]);
+    mock.add_build("deploy", None);
+    mock.add_input(
+        "deploy",
+        1,
+        "Prod",
+        "Deploy to production?",
+        &[("VERSION", "1.2.0")],
+    );
+    mock.add_input("deploy", 1, "Notify", "Notify the team?", &[]);
+
+    let output = jencli(&mock, &["input", "deploy"]);
+    assert_eq!(
+        stdout(&output),
+        "Prod\tDeploy to production?\n  VERSION=1.2.0\nNotify\tNotify the team?\n"
+    );
+
+    // several inputs are pending
+    let output = jencli(&mock, &["input", "deploy", "--proceed"]);
+    assert!(!output.status.success());
+
+    let output = jencli(
+        &mock,
+        &[
+            "input",
+            "deploy",
+            "--id",
+            "Prod",
+            "--proceed",
+            "-p",
+            "TAG=x",
+        ],
+    );
+    assert!(!output.status.success());
+
+    let output = jencli(
+        &mock,
+        &[
+            "input",
+            "deploy",
+            "1",
+            "--id",
+            "Prod",
+            "--proceed",
+            "-p",
+            "VERSION=1.3.0",
+        ],
+    );
+    assert_eq!(
+        stdout(&output),
+        "Prod\tDeploy to production?\tproceeded by anonymous VERSION=1.3.0\n"
+    );
+    assert_eq!(
+        mock.input_answers("deploy", 1),
+        vec![(
+            "Prod".to_string(),
+            serde_json::json!([{ "name": "VERSION", "value": "1.3.0" }])
+        )]
+    );
+
+    let output = jencli(&mock, &["input", "deploy", "--abort"]);
+    assert_eq!(
+        stdout(&output),
+        "Notify\tNotify the team?\taborted by anonymous\n"
+    );
+    let output = jencli(&mock, &["build", "deploy", "-t", "{{ result }}"]);
+    assert_eq!(stdout(&output), "ABORTED\n");
+}
+
+#[test]
+fn trigger_sends_parameters_from_defaults_files_and_uploads() {
+    let mock = MockJenkins::start();
+    mock.add_job("deploy", Lifecycle::default());
+    mock.add_parameter("deploy", "VERSION", "1.0.0");
+    mock.add_parameter("deploy", "ENV", "staging");
+    mock.add_file_parameter("deploy", "MANIFEST");
+    let wait = ["--wait-finish", "--polling", "0"];
+
+    // without parameters, the build uses the default values
+    let output = jencli(&mock, &[&["trigger", "deploy"][..], &wait].concat());
+    assert!(output.status.success());
+    assert!(mock
+        .requests()
+        .contains(&"POST /job/deploy/buildWithParameters".to_string()));
+    assert_eq!(
+        mock.build_parameters("deploy", 1),
+        vec![
+            ("ENV".to_string(), "staging".to_string()),
+            ("VERSION".to_string(), "1.0.0".to_string())
+        ]
+    );
+
+    let dir = std::env::temp_dir();
+    let params = dir.join(format!("jencli-params-{}.conf", std::process::id()));
+    std::fs::write(&params, "VERSION = \"2.0.0\"\nENV = prod\n").unwrap();
+    let manifest = dir.join(format!("jencli-manifest-{}.txt", std::process::id()));
+    std::fs::write(&manifest, "app: 2.0.0").unwrap();
+    let output = jencli(
+        &mock,
+        &[
+            &[
+                "trigger",
+                "deploy",
+                "--params-file",
+                params.to_str().unwrap(),
+                "-p",
+                "ENV=qa",
+            ][..],
+            &wait,
+        ]
+        .concat(),
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        mock.build_parameters("deploy", 2),
+        vec![
+            ("ENV".to_string(), "qa".to_string()),
+            ("VERSION".to_string(), "2.0.0".to_string())
+        ]
+    );
+
+    let manifest_param = format!("MANIFEST={}", manifest.display());
+    let output = jencli(
+        &mock,
+        &[
+            &["trigger", "deploy", "-f", &manifest_param, "-p", "ENV=prod"][..],
+            &wait,
+        ]
+        .concat(),
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        mock.build_parameters("deploy", 3),
+        vec![
+            ("ENV".to_string(), "prod".to_string()),
+            ("MANIFEST".to_string(), "app: 2.0.0".to_string()),
+            ("VERSION".to_string(), "1.0.0".to_string())
+        ]
+    );
+
+    // invalid parameters are rejected before triggering anything
+    let version_file = format!("VERSION={}", manifest.display());
+    for (args, error) in &[
+        (vec!["-p", "COLOR=red"], "unknown parameter 'COLOR'"),
+        (
+            vec!["-p", "MANIFEST=app"],
+            "parameter 'MANIFEST' expects a file",
+        ),
+        (
+            vec!["-f", &version_file],
+            "parameter 'VERSION' does not accept a file",
+        ),
+        (
+            vec!["-f", "MANIFEST=/nonexistent/manifest"],
+            "for parameter 'MANIFEST' does not exist",
+        ),
+    ] {
+        let output = jencli(&mock, &[&["trigger", "deploy"][..], args].concat());
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{}", stderr);
+    }
+    assert_eq!(
+        mock.requests()
+            .iter()
+            .filter(|request| request.starts_with("POST "))
+            .count(),
+        3
+    );
+    std::fs::remove_file(&params).unwrap();
+    std::fs::remove_file(&manifest).unwrap();
+}
+
+#[test]
+fn inputs_are_not_answered_without_knowing_the_user() {
+    let mock = MockJenkins::start();
+    mock.add_job("deploy", Lifecycle::default());
+    mock.set_stages("deploy", &["approve"]);
+    mock.add_build("deploy", None);
+    mock.add_input("deploy", 1, "Prod", "Deploy to production?", &[]);
+    mock.fail_requests_to("/me/api/json");
+
+    let output = jencli(&mock, &["input", "deploy", "--proceed"]);
+    assert_eq!(output.status.code(), Some(10));
+    assert!(mock.input_answers("deploy", 1).is_empty());
+}
+
+#[test]
+fn builds_are_rebuilt_and_replayed_with_their_parameters() {
+    let mock = MockJenkins::start();
+    mock.add_job("deploy", Lifecycle::default());
+    mock.set_stages("deploy", &["deploy"]);
+    mock.add_parameter("deploy", "VERSION", "1.0.0");
+    mock.add_parameter("deploy", "ENV", "staging");
+    let finished = "{{#if build}}{{#unless build.building}}{{ build.displayName }} {{ build.result }}{{/unless}}{{/if}}";
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "deploy",
+            "-p",
+            "VERSION=1.2.0",
+            "-p",
+            "ENV=prod",
+            "--wait-finish",
+            "--polling",
+            "0",
+        ],
+    );
+    assert!(output.status.success());
+
+    let output = jencli(
+        &mock,
+        &[
+            "rebuild",
+            "deploy",
+            "1",
+            "-p",
+            "ENV=qa",
+            "--wait-finish",
+            "--polling",
+            "0",
+            "-t",
+            finished,
+        ],
+    );
+    assert!(
+        stdout(&output).ends_with("#2 SUCCESS\n"),
+        "{}",
+        stdout(&output)
+    );
+    assert_eq!(
+        mock.build_parameters("deploy", 2),
+        vec![
+            ("ENV".to_string(), "qa".to_string()),
+            ("VERSION".to_string(), "1.2.0".to_string())
+        ]
+    );
+
+    let script = std::env::temp_dir().join(format!("jencli-replay-{}.groovy", std::process::id()));
+    std::fs::write(&script, "node { sh 'make deploy' }\n").unwrap();
+    // a build queued by someone else is not mistaken for the replay
+    let other = mock.enqueue("deploy");
+    let output = jencli(
+        &mock,
+        &[
+            "replay",
+            "deploy",
+            "--script",
+            script.to_str().unwrap(),
+            "--wait-finish",
+            "--polling",
+            "0",
+            "-t",
+            finished,
+        ],
+    );
+    assert!(
+        stdout(&output).ends_with("#3 SUCCESS\n"),
+        "{}",
+        stdout(&output)
+    );
+    assert_eq!(
+        mock.script("deploy", 3).as_deref(),
+        Some("node { sh 'make deploy' }\n")
+    );
+    assert_eq!(
+        mock.build_parameters("deploy", 3),
+        mock.build_parameters("deploy", 2)
+    );
+    mock.cancel(other);
+
+    mock.add_job("tools", Lifecycle::default());
+    mock.add_build("tools", Some(BuildStatus::Success));
+    let output = jencli(
+        &mock,
+        &["replay", "tools", "--script", script.to_str().unwrap()],
+    );
+    assert!(!output.status.success());
+
+    std::fs::remove_file(script).unwrap();
+}
+
+#[test]
+fn batch_waits_for_all_builds() {
+    let mock = MockJenkins::start();
+    for (name, running_polls, result) in &[
+        ("lib-core", 1, BuildStatus::Success),
+        ("lib-http", 3, BuildStatus::Unstable),
+        ("lib-json", 2, BuildStatus::Success),
+    ] {
+        mock.add_job(
+            name,
+            Lifecycle {
+                queued_polls: 1,
+                running_polls: *running_polls,
+                result: *result,
+            },
+        );
+    }
+
+    let output = jencli(
+        &mock,
+        &[
+            "-o",
+            "table",
+            "--columns",
+            "name,number,status",
+            "batch",
+            "--all-matching",
+            "^lib-",
+            "--max-parallel",
+            "2",
+            "--polling",
+            "0",
+        ],
+    );
+
+    assert_eq!(
+        stdout(&output),
+        "NAME      NUMBER  STATUS\n\
+         lib-core  1       SUCCESS\n\
+         lib-http  1       UNSTABLE\n\
+         lib-json  1       SUCCESS\n"
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let progress = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        progress.contains("1/3 finished: lib-core #1 SUCCESS, lib-http #1 "),
+        "{}",
+        progress
+    );
+}
+
+#[test]
+fn batch_fails_fast() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "deploy-db",
+        Lifecycle {
+            queued_polls: 1,
+            running_polls: 1,
+            result: BuildStatus::Failure,
+        },
+    );
+    mock.add_job(
+        "deploy-api",
+        Lifecycle {
+            queued_polls: 1,
+            running_polls: 100,
+            result: BuildStatus::Success,
+        },
+    );
+    mock.add_job("deploy-web", Lifecycle::default());
+    let jobs = std::env::temp_dir().join(format!("jencli-jobs-{}.txt", std::process::id()));
+    std::fs::write(&jobs, "# in order\ndeploy-db\ndeploy-api\n\ndeploy-web\n").unwrap();
+
+    let output = jencli(
+        &mock,
+        &[
+            "batch",
+            "--jobs-file",
+            jobs.to_str().unwrap(),
+            "--max-parallel",
+            "2",
+            "--fail-fast",
+            "--polling",
+            "0",
+            "-t",
+            "{{ name }} {{ status }}",
+        ],
+    );
+
+    assert_eq!(
+        stdout(&output),
+        "deploy-db FAILURE\ndeploy-api ABORTED\ndeploy-web SKIPPED\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    std::fs::remove_file(jobs).unwrap();
+}
+
+#[test]
+fn batch_retries_server_errors_and_keeps_stopping_jobs() {
+    let mock = MockJenkins::start();
+    for (name, running_polls, result) in &[
+        ("job-failing", 1, BuildStatus::Failure),
+        ("job-locked", 5, BuildStatus::Success),
+        ("job-long", 100, BuildStatus::Success),
+    ] {
+        mock.add_job(
+            name,
+            Lifecycle {
+                queued_polls: 1,
+                running_polls: *running_polls,
+                result: *result,
+            },
+        );
+    }
+    mock.fail_requests_to("/job/job-locked/1/stop");
+    mock.fail_queue_polls(1);
+
+    let output = jencli(
+        &mock,
+        &[
+            "batch",
+            "--all-matching",
+            "^job-",
+            "--fail-fast",
+            "--polling",
+            "0",
+            "-t",
+            "{{ name }} {{ status }}",
+        ],
+    );
+
+    assert_eq!(
+        stdout(&output),
+        "job-failing FAILURE\njob-locked SUCCESS\njob-long ABORTED\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let progress = String::from_utf8_lossy(&output.stderr);
+    assert!(progress.contains("retrying in 1s"), "{}", progress);
+    assert!(
+        progress.contains("error: could not stop job-locked"),
+        "{}",
+        progress
+    );
+    // each build is asked to stop once, then only checked until it ends
+    for stop in &["POST /job/job-locked/1/stop", "POST /job/job-long/1/stop"] {
+        assert_eq!(
+            mock.requests()
+                .iter()
+                .filter(|request| request == stop)
+                .count(),
+            1,
+            "{}",
+            stop
+        );
+    }
+}
+
+#[test]
+fn batch_keeps_going_when_a_job_can_not_be_triggered() {
+    let mock = MockJenkins::start();
+    mock.add_job("first", Lifecycle::default());
+    mock.add_job("last", Lifecycle::default());
+
+    let output = jencli(
+        &mock,
+        &[
+            "batch",
+            "first",
+            "missing",
+            "last",
+            "--max-parallel",
+            "1",
+            "--polling",
+            "0",
+            "-t",
+            "{{ name }} {{ status }}",
+        ],
+    );
+
+    assert_eq!(
+        stdout(&output),
+        "first SUCCESS\nmissing ERROR\nlast SUCCESS\n"
+    );
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: could not trigger missing: "),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn trigger_stops_waiting_on_timeout_or_cancellation() {
+    let mock = Arc::new(MockJenkins::start());
+    let stuck = Lifecycle {
+        queued_polls: 1000,
+        running_polls: 1000,
+        result: BuildStatus::Success,
+    };
+    mock.add_job("queued", stuck);
+    mock.add_job(
+        "running",
+        Lifecycle {
+            queued_polls: 0,
+            ..stuck
+        },
+    );
+    mock.add_job("cancelled", stuck);
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "queued",
+            "--wait-start",
+            "--polling",
+            "1",
+            "--queue-timeout",
+            "1s",
+            "--abort-on-timeout",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(5));
+    assert!(mock
+        .requests()
+        .contains(&"POST /queue/cancelItem?id=1".to_string()));
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "running",
+            "--wait-finish",
+            "--polling",
+            "1",
+            "--build-timeout",
+            "1s",
+            "--abort-on-timeout",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(5));
+    let output = jencli(&mock, &["build", "running", "-t", "{{ result }}"]);
+    assert_eq!(stdout(&output), "ABORTED\n");
+
+    let canceller = {
+        let mock = Arc::clone(&mock);
+        thread::spawn(move || {
+            while !mock
+                .requests()
+                .iter()
+                .any(|request| request.contains("/queue/item/3/"))
+            {
+                thread::sleep(Duration::from_millis(10));
+            }
+            mock.cancel(3);
+        })
+    };
+    let output = jencli(
+        &mock,
+        &["trigger", "cancelled", "--wait-finish", "--polling", "1"],
+    );
+    canceller.join().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "running",
+            "--log",
+            "--polling",
+            "1",
+            "--build-timeout",
+            "1s",
+            "--abort-on-timeout",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(5));
+    let output = jencli(&mock, &["build", "running", "2", "-t", "{{ result }}"]);
+    assert_eq!(stdout(&output), "ABORTED\n");
+
+    let output = jencli(
+        &mock,
+        &[
+            "rebuild",
+            "running",
+            "--wait-finish",
+            "--polling",
+            "1",
+            "--build-timeout",
+            "1s",
+            "--abort-on-timeout",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(5));
+    let output = jencli(&mock, &["build", "running", "3", "-t", "{{ result }}"]);
+    assert_eq!(stdout(&output), "ABORTED\n");
+}
+
+/// Run jencli, pressing Ctrl-C and answering `y` once `ready` returns true
+/// and jencli wrote a line containing `waiting` on stderr
+fn interrupt_jencli(
+    mock: &MockJenkins,
+    args: &[&str],
+    ready: impl Fn() -> bool,
+    waiting: &str,
+) -> Output {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::process::Stdio;
+
+    let home = TempHome::new();
+    let mut child = command(mock, &home)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("could not run jencli");
+    while !ready() {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut lines = String::new();
+    while !lines.contains(waiting) {
+        if stderr.read_line(&mut lines).unwrap() == 0 {
+            break;
+        }
+    }
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+    stderr.read_to_string(&mut lines).unwrap();
+    Output {
+        stderr: lines.into_bytes(),
+        ..child.wait_with_output().unwrap()
+    }
+}
+
+#[test]
+fn ctrl_c_offers_to_abort_while_waiting_or_retrying() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "app",
+        Lifecycle {
+            queued_polls: 1000,
+            running_polls: 1000,
+            result: BuildStatus::Success,
+        },
+    );
+    let polls = |id: u32| {
+        mock.requests()
+            .iter()
+            .filter(|request| request.starts_with(&format!("GET /queue/item/{}/", id)))
+            .count()
+    };
+
+    // the second poll fails, and jencli sleeps before retrying
+    let output = interrupt_jencli(
+        &mock,
+        &["trigger", "app", "--wait-finish", "--polling", "1"],
+        || match polls(1) {
+            0 => false,
+            1 => {
+                mock.fail_queue_polls(10);
+                false
+            }
+            _ => true,
+        },
+        "retrying in",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert!(mock
+        .requests()
+        .contains(&"POST /queue/cancelItem?id=1".to_string()));
+
+    mock.fail_queue_polls(0);
+    let output = interrupt_jencli(
+        &mock,
+        &[
+            "batch",
+            "app",
+            "--polling",
+            "1",
+            "-t",
+            "{{ name }} {{ status }}",
+        ],
+        || true,
+        "0/1 finished: app queued",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "app CANCELLED\n");
+}
+
+#[test]
+fn trigger_retries_on_server_errors_and_shows_eta() {
+    let mock = MockJenkins::start();
+    mock.add_job(
+        "app",
+        Lifecycle {
+            queued_polls: 0,
+            running_polls: 1000,
+            result: BuildStatus::Success,
+        },
+    );
+    mock.fail_queue_polls(2);
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "app",
+            "--wait-start",
+            "-t",
+            "{{#if build}}{{ build.eta }} {{ build.remaining }}{{/if}}",
+        ],
+    );
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Jenkins returned 503"));
+    assert!(stderr.contains("retrying in 1s"));
+    assert!(stderr.contains("retrying in 2s"));
+
+    let build = jencli::get_build(&mock.jenkins(), "app", Some(1)).unwrap();
+    let eta = (build.timestamp as i64 + build.estimated_duration) / 1000;
+    // the mock clock is in the past, so the build is already late
+    assert_eq!(stdout(&output), format!("{} 0\n", eta));
+}
+
+#[test]
+fn credentials_are_only_read_to_contact_jenkins() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    let missing = std::env::temp_dir().join(format!("jencli-no-token-{}", std::process::id()));
+    let missing = missing.to_str().unwrap();
+
+    let output = jencli(&mock, &["--token-file", missing, "completions", "bash"]);
+    assert!(output.status.success());
+
+    let output = jencli(&mock, &["--token-file", missing, "job", "app"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn login_stores_a_private_token_used_by_later_commands() {
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Stdio;
+
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    let home = TempHome::new();
+    let token = home.path().join(".config/jencli/tokens/default");
+    let login = || {
+        let mut child = command(&mock, &home)
+            .args(["--user", "alice", "login"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("could not run jencli");
+        child.stdin.take().unwrap().write_all(b"secret\n").unwrap();
+        child.wait_with_output().unwrap()
+    };
+    let mode = || std::fs::metadata(&token).unwrap().permissions().mode() & 0o777;
+
+    let output = login();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_to_string(&token).unwrap(), "secret\n");
+    assert_eq!(mode(), 0o600);
+
+    let output = jencli_in(&home, &mock, &["--user", "alice", "job", "app"]);
+    assert!(output.status.success());
+    assert_eq!(
+        mock.authorization().as_deref(),
+        Some("Basic YWxpY2U6c2VjcmV0")
+    );
+
+    // a token readable by other users is refused, until stored again
+    std::fs::set_permissions(&token, std::fs::Permissions::from_mode(0o644)).unwrap();
+    let output = jencli_in(&home, &mock, &["--user", "alice", "job", "app"]);
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("is accessible by other users"),
+        "{}",
+        stderr
+    );
+    assert!(login().status.success());
+    assert_eq!(mode(), 0o600);
+}
+
+#[test]
+fn password_command_gives_the_password() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    let home = TempHome::new();
+    std::fs::write(
+        home.path().join(".jencli.conf"),
+        "user = bob\npassword_command = \"echo hunter2\"\n",
+    )
+    .unwrap();
+
+    let output = jencli_in(&home, &mock, &["job", "app"]);
+    assert!(output.status.success());
+    assert_eq!(
+        mock.authorization().as_deref(),
+        Some("Basic Ym9iOmh1bnRlcjI=")
+    );
+
+    std::fs::write(
+        home.path().join(".jencli.conf"),
+        "user = bob\npassword_command = \"echo locked >&2; exit 1\"\n",
+    )
+    .unwrap();
+    let output = jencli_in(&home, &mock, &["job", "app"]);
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("failed: locked"), "{}", stderr);
+}
+
+#[test]
+fn profiles_are_selected_and_merged_with_root_settings() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    let home = TempHome::new();
+    let conf = home.path().join(".jencli.conf");
+    std::fs::write(
+        &conf,
+        "user = root-user\n\
+         password = pw\n\
+         profile = staging\n\
+         profiles.staging { user = stage-user }\n\
+         profiles.prod { user = prod-user, depth = 2 }\n",
+    )
+    .unwrap();
+    let user_of = |command: &mut Command| {
+        assert!(command.output().unwrap().status.success());
+        mock.authorization()
+    };
+    let stage = Some("Basic c3RhZ2UtdXNlcjpwdw==".to_string());
+    let prod = Some("Basic cHJvZC11c2VyOnB3".to_string());
+
+    assert_eq!(user_of(command(&mock, &home).args(["job", "app"])), stage);
+    assert_eq!(
+        user_of(command(&mock, &home).args(["--profile", "prod", "job", "app"])),
+        prod
+    );
+    assert_eq!(
+        user_of(
+            command(&mock, &home)
+                .env("JENKINS_PROFILE", "prod")
+                .args(["job", "app"])
+        ),
+        prod
+    );
+    // options still override the profile
+    assert_eq!(
+        user_of(command(&mock, &home).args(["--profile", "prod", "--user", "bob", "job", "app"])),
+        Some("Basic Ym9iOnB3".to_string())
+    );
+
+    let output = jencli_in(&home, &mock, &["--profile", "qa", "job", "app"]);
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("profile 'qa' is not configured"),
+        "{}",
+        stderr
+    );
+
+    let output = jencli_in(&home, &mock, &["-o", "json", "profiles"]);
+    let profiles = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0]["name"], "prod");
+    assert_eq!(profiles[0]["user"], "prod-user");
+    assert_eq!(profiles[0]["depth"], 2);
+    assert_eq!(profiles[0]["default"], false);
+    assert_eq!(profiles[1]["name"], "staging");
+    assert_eq!(profiles[1]["default"], true);
+    assert_eq!(
+        profiles[1]["sources"],
+        serde_json::json!([conf.to_str().unwrap()])
+    );
+    assert!(profiles[1].get("password").is_none());
+}
+
+#[test]
+fn settings_are_read_from_the_environment() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    let home = TempHome::new();
+
+    let output = command(&mock, &home)
+        .env("JENKINS_USER", "bob")
+        .env("JENKINS_PASSWORD_COMMAND", "echo hunter2")
+        .args(["job", "app"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        mock.authorization().as_deref(),
+        Some("Basic Ym9iOmh1bnRlcjI=")
+    );
+
+    // the password comes first, but the command is still a valid setting
+    let output = command(&mock, &home)
+        .env("JENKINS_USER", "bob")
+        .env("JENKINS_PASSWORD", "pw")
+        .env("JENKINS_PASSWORD_COMMAND", "echo hunter2")
+        .args(["job", "app"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(mock.authorization().as_deref(), Some("Basic Ym9iOnB3"));
+}
+
+#[test]
+fn document_formats_are_written_once() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+
+    let output = jencli(&mock, &["-o", "json-array", "trigger", "app", "--log"]);
+    assert!(!output.status.success());
+    assert!(mock
+        .requests()
+        .iter()
+        .all(|request| !request.contains("/build")));
+
+    let output = jencli(&mock, &["-o", "csv", "--watch", "1", "job", "app"]);
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn jenkins_under_a_context_path() {
+    let mock = MockJenkins::start_under("/jenkins");
+    mock.add_job("app", Lifecycle::default());
+
+    let output = jencli(
+        &mock,
+        &[
+            "trigger",
+            "app",
+            "--wait-finish",
+            "--polling",
+            "0",
+            "-t",
+            "{{#if build}}{{ build.result }}{{/if}}",
+        ],
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("SUCCESS\n"));
+    assert!(mock
+        .requests()
+        .iter()
+        .all(|request| request.contains(" /jenkins/") && !request.contains("/jenkins/jenkins/")));
+}
+
+#[test]
+fn builds_until_a_day_include_that_day() {
+    let mock = MockJenkins::start();
+    mock.add_job("app", Lifecycle::default());
+    // builds of the mock start on 2020-09-13
+    mock.add_build("app", Some(BuildStatus::Success));
+    mock.add_build("app", Some(BuildStatus::Failure));
+
+    let output = jencli(
+        &mock,
+        &[
+            "builds",
+            "app",
+            "--until",
+            "2020-09-13",
+            "-t",
+            "{{ number }}",
+        ],
+    );
+    assert_eq!(stdout(&output), "2\n1\n");
+
+    let output = jencli(
+        &mock,
+        &[
+            "builds",
+            "app",
+            "--until",
+            "2020-09-12",
+            "-t",
+            "{{ number }}",
+        ],
+    );
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn watching_until_a_result_only_looks_at_the_newest_build() {
+    let mock = Arc::new(MockJenkins::start());
+    mock.add_job("app", Lifecycle::default());
+    mock.add_build("app", None);
+
+    let output = jencli(
+        &mock,
+        &["--watch", "1", "--until-result", "success", "build", "app"],
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).matches("Every 1s").count() > 1);
+
+    mock.add_build("app", None);
+    let ticker = {
+        let mock = Arc::clone(&mock);
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(1500));
+            mock.tick();
+            mock.tick();
+        })
+    };
+    let output = jencli(
+        &mock,
+        &["--watch", "1", "--until-result", "success", "builds", "app"],
+    );
+    ticker.join().unwrap();
+    assert!(output.status.success());
+    assert!(stdout(&output).matches("Every 1s").count() > 1);
+
+    let output = jencli(
+        &mock,
+        &["--watch", "1", "--until-result", "success", "running"],
+    );
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "");
+}